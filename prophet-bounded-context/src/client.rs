@@ -0,0 +1,295 @@
+use std::{
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use actix_web::client::{Client, Connector};
+use openssl::ssl::{SslConnector, SslMethod, SslVerifyMode};
+use serde::{Deserialize, Serialize};
+
+use crate::{Error, RemoteCallError};
+
+/// The longest delay between two retries of a request
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+/// The TLS settings for connecting to the bounded context service over HTTPS
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TlsSettings {
+    /// A PEM file of additional CA certificates to trust
+    pub ca_file: Option<PathBuf>,
+    /// Whether to skip verifying the certificate of the service. Only use this for testing
+    pub accept_invalid_certs: bool,
+}
+
+/// When to stop calling the bounded context service after repeated failures
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CircuitBreakerSettings {
+    /// How many consecutive failed requests open the circuit
+    pub failure_threshold: u32,
+    /// How long the circuit stays open before a request is tried again, in seconds
+    pub reset_secs: u64,
+}
+
+impl Default for CircuitBreakerSettings {
+    fn default() -> Self {
+        CircuitBreakerSettings {
+            failure_threshold: 5,
+            reset_secs: 30,
+        }
+    }
+}
+
+/// The state of the circuit breaker, shared by all clones of a client
+#[derive(Debug, Default)]
+struct CircuitState {
+    failures: u32,
+    open_until: Option<Instant>,
+    /// Whether the circuit was open and is letting requests through to check
+    /// whether the service recovered, in which case one failure opens it again
+    half_open: bool,
+}
+
+/// The configuration of the client for the external bounded context service
+///
+/// The serialized representation in JSON is as follows, where every field is optional
+/// ```json
+/// {
+///   "base_url": "https://bounded-context.internal:8081/",
+///   "timeout_secs": 60,
+///   "retries": 2,
+///   "backoff_ms": 500,
+///   "max_response_bytes": 16777216,
+///   "circuit_breaker": {
+///     "failure_threshold": 5,
+///     "reset_secs": 30
+///   },
+///   "tls": {
+///     "ca_file": "/etc/prophet/ca.pem",
+///     "accept_invalid_certs": false
+///   }
+/// }
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BoundedContextClient {
+    /// The URL the bounded context service accepts merge requests at
+    pub base_url: String,
+    /// How long to wait for the service to respond to a request, in seconds
+    pub timeout_secs: u64,
+    /// How many times to retry a request that failed to connect or got a 5xx response
+    pub retries: u32,
+    /// How long to wait before the first retry in milliseconds, doubling for every
+    /// following retry
+    pub backoff_ms: u64,
+    /// The largest response body accepted from the service, in bytes
+    pub max_response_bytes: usize,
+    /// When to stop calling the service after repeated failures
+    pub circuit_breaker: CircuitBreakerSettings,
+    /// The TLS settings for a `base_url` using HTTPS
+    pub tls: TlsSettings,
+    #[serde(skip)]
+    circuit: Arc<Mutex<CircuitState>>,
+}
+
+impl Default for BoundedContextClient {
+    fn default() -> Self {
+        BoundedContextClient {
+            base_url: "http://127.0.0.1:8081/".into(),
+            timeout_secs: 60,
+            retries: 2,
+            backoff_ms: 500,
+            max_response_bytes: 16 * 1024 * 1024,
+            circuit_breaker: CircuitBreakerSettings::default(),
+            tls: TlsSettings::default(),
+            circuit: Arc::default(),
+        }
+    }
+}
+
+impl BoundedContextClient {
+    /// Loads the configuration from the `PROPHET_BC_URL`, `PROPHET_BC_TIMEOUT_SECS`,
+    /// `PROPHET_BC_RETRIES`, `PROPHET_BC_BACKOFF_MS`, `PROPHET_BC_MAX_RESPONSE_BYTES`,
+    /// `PROPHET_BC_CA_FILE` and `PROPHET_BC_ACCEPT_INVALID_CERTS` environment variables, using the defaults
+    /// for those that are not set
+    pub fn from_env() -> Result<BoundedContextClient, Error> {
+        let mut client = BoundedContextClient::default();
+        if let Some(base_url) = parse_var("PROPHET_BC_URL")? {
+            client.base_url = base_url;
+        }
+        if let Some(timeout_secs) = parse_var("PROPHET_BC_TIMEOUT_SECS")? {
+            client.timeout_secs = timeout_secs;
+        }
+        if let Some(retries) = parse_var("PROPHET_BC_RETRIES")? {
+            client.retries = retries;
+        }
+        if let Some(backoff_ms) = parse_var("PROPHET_BC_BACKOFF_MS")? {
+            client.backoff_ms = backoff_ms;
+        }
+        if let Some(max_response_bytes) = parse_var("PROPHET_BC_MAX_RESPONSE_BYTES")? {
+            client.max_response_bytes = max_response_bytes;
+        }
+        if let Some(ca_file) = parse_var("PROPHET_BC_CA_FILE")? {
+            client.tls.ca_file = Some(ca_file);
+        }
+        if let Some(accept_invalid_certs) = parse_var("PROPHET_BC_ACCEPT_INVALID_CERTS")? {
+            client.tls.accept_invalid_certs = accept_invalid_certs;
+        }
+        Ok(client)
+    }
+
+    /// Loads the configuration from a JSON file
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<BoundedContextClient, Error> {
+        let file = std::fs::File::open(path.as_ref())
+            .map_err(|err| Error::Config(format!("Could not read {:?}: {}", path.as_ref(), err)))?;
+        serde_json::from_reader(file).map_err(|err| Error::Config(err.to_string()))
+    }
+
+    /// The timeout of a single request to the service
+    pub fn timeout(&self) -> Duration {
+        Duration::from_secs(self.timeout_secs)
+    }
+
+    /// How long to wait before retrying a request for the given time
+    pub(crate) fn backoff(&self, retry: u32) -> Duration {
+        Duration::from_millis(self.backoff_ms)
+            .saturating_mul(2u32.saturating_pow(retry))
+            .min(MAX_BACKOFF)
+    }
+
+    /// Fails fast if the circuit is open because of repeated failures
+    pub(crate) fn check_circuit(&self) -> Result<(), RemoteCallError> {
+        let mut circuit = self.circuit.lock().unwrap();
+        match circuit.open_until {
+            Some(open_until) if Instant::now() < open_until => Err(RemoteCallError::CircuitOpen),
+            Some(_) => {
+                // Let requests through to check whether the service recovered
+                circuit.open_until = None;
+                circuit.half_open = true;
+                Ok(())
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Records the outcome of a request, opening the circuit after too many
+    /// consecutive failures, or after any failure while it is half open
+    pub(crate) fn record(&self, success: bool) {
+        let mut circuit = self.circuit.lock().unwrap();
+        if success {
+            *circuit = CircuitState::default();
+            return;
+        }
+
+        circuit.failures += 1;
+        if circuit.half_open || circuit.failures >= self.circuit_breaker.failure_threshold {
+            circuit.failures = 0;
+            circuit.half_open = false;
+            circuit.open_until =
+                Some(Instant::now() + Duration::from_secs(self.circuit_breaker.reset_secs));
+        }
+    }
+
+    /// Creates the HTTP client to call the service with
+    pub(crate) fn client(&self) -> Result<Client, Error> {
+        let connector = Connector::new()
+            .ssl(self.ssl_connector()?)
+            .timeout(self.timeout())
+            .finish();
+        Ok(Client::builder()
+            .connector(connector)
+            .timeout(self.timeout())
+            .finish())
+    }
+
+    fn ssl_connector(&self) -> Result<SslConnector, Error> {
+        let config_error = |err: openssl::error::ErrorStack| Error::Config(err.to_string());
+
+        let mut builder = SslConnector::builder(SslMethod::tls()).map_err(config_error)?;
+        if let Some(ca_file) = &self.tls.ca_file {
+            builder.set_ca_file(ca_file).map_err(config_error)?;
+        }
+        if self.tls.accept_invalid_certs {
+            builder.set_verify(SslVerifyMode::NONE);
+        }
+        Ok(builder.build())
+    }
+}
+
+/// Parses an environment variable, if it is set
+fn parse_var<T: FromStr>(name: &str) -> Result<Option<T>, Error> {
+    match std::env::var(name) {
+        Ok(value) if !value.is_empty() => value
+            .parse()
+            .map(Some)
+            .map_err(|_| Error::Config(format!("Invalid value for {}: {}", name, value))),
+        _ => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn client(failure_threshold: u32, reset_secs: u64) -> BoundedContextClient {
+        BoundedContextClient {
+            circuit_breaker: CircuitBreakerSettings {
+                failure_threshold,
+                reset_secs,
+            },
+            ..BoundedContextClient::default()
+        }
+    }
+
+    #[test]
+    fn double_backoff_up_to_maximum() {
+        let client = BoundedContextClient::default();
+        assert_eq!(Duration::from_millis(500), client.backoff(0));
+        assert_eq!(Duration::from_secs(1), client.backoff(1));
+        assert_eq!(Duration::from_secs(2), client.backoff(2));
+        assert_eq!(MAX_BACKOFF, client.backoff(10));
+        assert_eq!(MAX_BACKOFF, client.backoff(u32::MAX));
+    }
+
+    #[test]
+    fn open_circuit_after_consecutive_failures() {
+        let client = client(2, 3600);
+        client.record(false);
+        client.record(true);
+        client.record(false);
+        assert!(client.check_circuit().is_ok());
+
+        client.record(false);
+        assert!(matches!(
+            client.check_circuit(),
+            Err(RemoteCallError::CircuitOpen)
+        ));
+    }
+
+    #[test]
+    fn reopen_half_open_circuit_after_one_failure() {
+        // The circuit is half open as soon as it opened
+        let client = client(2, 0);
+        client.record(false);
+        client.record(false);
+        assert!(client.check_circuit().is_ok());
+        assert!(client.circuit.lock().unwrap().half_open);
+
+        client.record(false);
+        let circuit = client.circuit.lock().unwrap();
+        assert!(circuit.open_until.is_some());
+        assert!(!circuit.half_open);
+        drop(circuit);
+
+        // A successful request closes the circuit again
+        assert!(client.check_circuit().is_ok());
+        client.record(true);
+        client.record(false);
+        let circuit = client.circuit.lock().unwrap();
+        assert!(circuit.open_until.is_none());
+        assert_eq!(1, circuit.failures);
+    }
+}
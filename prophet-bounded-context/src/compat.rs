@@ -1,5 +1,5 @@
 use derive_new::new;
-use prophet_model::{DatabaseType, Entity, Field};
+use prophet_model::{Entity, EntitySource, Field};
 use serde::{Deserialize, Serialize};
 
 /// Request DTO:
@@ -74,7 +74,7 @@ impl From<Field> for BoundedContextField {
 }
 
 /// Response DTO:
-#[derive(Deserialize)]
+#[derive(new, Deserialize)]
 #[serde(rename_all(deserialize = "camelCase"))]
 pub(crate) struct MergedEntitySystem {
     #[allow(unused)]
@@ -82,14 +82,24 @@ pub(crate) struct MergedEntitySystem {
     bounded_context_entities: Vec<MergedEntity>,
 }
 
-#[derive(Deserialize)]
+#[derive(new, Deserialize)]
 #[serde(rename_all(deserialize = "camelCase"))]
 pub(crate) struct MergedEntity {
     entity_name: MergedName,
     fields: Vec<MergedField>,
+    /// The entities that were merged, which the external service does not return
+    #[serde(skip)]
+    #[new(default)]
+    pub(crate) sources: Vec<EntitySource>,
 }
 
-#[derive(Deserialize)]
+impl MergedEntity {
+    pub(crate) fn name(&self) -> &str {
+        &self.entity_name.full_name
+    }
+}
+
+#[derive(new, Deserialize)]
 #[serde(rename_all(deserialize = "camelCase"))]
 pub(crate) struct MergedName {
     #[allow(unused)]
@@ -97,7 +107,7 @@ pub(crate) struct MergedName {
     full_name: String,
 }
 
-#[derive(Deserialize)]
+#[derive(new, Deserialize)]
 #[serde(rename_all(deserialize = "camelCase"))]
 pub(crate) struct MergedField {
     name: MergedName,
@@ -105,6 +115,16 @@ pub(crate) struct MergedField {
     #[allow(unused)]
     reference: bool,
     collection: bool,
+    /// The fields that were merged, which the external service does not return
+    #[serde(skip)]
+    #[new(default)]
+    pub(crate) sources: Vec<Field>,
+}
+
+impl MergedEntitySystem {
+    pub(crate) fn entities_mut(&mut self) -> &mut [MergedEntity] {
+        &mut self.bounded_context_entities
+    }
 }
 
 impl From<MergedEntitySystem> for Vec<Entity> {
@@ -117,19 +137,15 @@ impl From<MergedEntitySystem> for Vec<Entity> {
 }
 impl From<MergedEntity> for Entity {
     fn from(me: MergedEntity) -> Self {
-        Entity {
-            name: me.entity_name.full_name,
-            fields: me.fields.into_iter().map(|field| field.into()).collect(),
-            ty: DatabaseType::Unknown(String::new()),
-        }
+        Entity::merged(
+            me.entity_name.full_name,
+            me.fields.into_iter().map(|field| field.into()).collect(),
+            me.sources,
+        )
     }
 }
 impl From<MergedField> for Field {
     fn from(mf: MergedField) -> Self {
-        Field {
-            name: mf.name.full_name,
-            ty: mf.r#type,
-            is_collection: mf.collection,
-        }
+        Field::new(mf.name.full_name, mf.r#type, mf.collection).merge_metadata(&mf.sources)
     }
 }
@@ -1,78 +1,241 @@
-use actix_web::{client::Client, http::StatusCode};
-use once_cell::sync::OnceCell;
+use actix_web::{
+    client::{Client, ConnectError, PayloadError, SendRequestError},
+    http::StatusCode,
+};
 use prophet_model::{Entity, EntityGraph};
+use serde::Serialize;
 
 use compat::*;
-use structopt::StructOpt;
 pub(crate) mod compat;
+pub(crate) mod native;
 
-/// CL arguments to set location of bounded-context service
-#[derive(StructOpt)]
-struct Opt {
-    #[structopt(long, short, default_value = "127.0.0.1")]
-    host: String,
-    #[structopt(long, short, default_value = "8081")]
-    port: i32,
-}
-static OPTS: OnceCell<Opt> = OnceCell::new();
+pub(crate) mod options;
+pub use options::*;
 
-/// Retrieve CL arguments identifying bounded-context service
-fn get_opts() -> &'static Opt {
-    OPTS.get_or_init(Opt::from_args)
-}
+pub(crate) mod client;
+pub use client::*;
 
 #[derive(Debug, Clone, thiserror::Error)]
 pub enum Error {
     #[error("ReSSA Error: {0}")]
     Ressa(String),
     #[error("Remote Call Error: {0}")]
-    RemoteCall(String),
-    #[error("Deserialization Error: {0}")]
-    Deserialize(String),
+    RemoteCall(#[from] RemoteCallError),
     #[error("Conversion to Graph Failed")]
     Conversion,
+    #[error("Invalid Options: {0}")]
+    InvalidOptions(String),
+    #[error("Invalid Client Configuration: {0}")]
+    Config(String),
+}
+
+/// The ways a call to the external bounded context service can fail
+#[derive(Debug, Clone, Serialize, thiserror::Error)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum RemoteCallError {
+    #[error("The service did not respond in time")]
+    Timeout,
+    #[error("Could not connect to the service: {message}")]
+    Connection { message: String },
+    #[error("The service responded with HTTP {status}: {body}")]
+    Status { status: u16, body: String },
+    #[error("Could not decode the response of the service: {message}")]
+    Decode { message: String },
+    #[error("The service is not called after repeated failures")]
+    CircuitOpen,
+    #[error("Could not send the request: {message}")]
+    Request { message: String },
+}
+
+impl RemoteCallError {
+    /// Whether the failure may be resolved by retrying the call
+    fn is_retryable(&self) -> bool {
+        match self {
+            RemoteCallError::Connection { .. } => true,
+            RemoteCallError::Status { status, .. } => *status >= 500,
+            _ => false,
+        }
+    }
+
+    /// Whether the failure indicates the service is unavailable
+    fn is_unavailable(&self) -> bool {
+        self.is_retryable() || matches!(self, RemoteCallError::Timeout)
+    }
 }
 
-/// Convert the ReSSA's output into a bounded context, using an external service
-pub async fn get_bounded_context(entities: &[Entity]) -> Result<EntityGraph, Error> {
-    let req = BoundedContextRequest::new(
-        BoundedContextSystem::new("dummy".to_string(), entities),
-        false,
-    );
-    let entities: Vec<Entity> = retrieve(req).await?.into();
+impl From<PayloadError> for RemoteCallError {
+    fn from(err: PayloadError) -> Self {
+        match err {
+            // The connection broke while the body was being received
+            PayloadError::Incomplete(_) | PayloadError::Io(_) => RemoteCallError::Connection {
+                message: err.to_string(),
+            },
+            // The body was received but is too large or cannot be read, which retrying
+            // will not change
+            err => RemoteCallError::Decode {
+                message: err.to_string(),
+            },
+        }
+    }
+}
+
+impl From<SendRequestError> for RemoteCallError {
+    fn from(err: SendRequestError) -> Self {
+        match err {
+            SendRequestError::Timeout | SendRequestError::Connect(ConnectError::Timeout) => {
+                RemoteCallError::Timeout
+            }
+            SendRequestError::Connect(err) => RemoteCallError::Connection {
+                message: err.to_string(),
+            },
+            err => RemoteCallError::Request {
+                message: err.to_string(),
+            },
+        }
+    }
+}
+
+/// Convert the ReSSA's output into a bounded context, merging the entities
+/// with the provided options. The client is only used by the remote strategy
+pub async fn get_bounded_context(
+    entities: &[Entity],
+    options: &BoundedContextOptions,
+    client: &BoundedContextClient,
+) -> Result<EntityGraph, Error> {
+    options.validate()?;
+
+    let merged = match options.strategy {
+        MergeStrategy::Remote => {
+            let req = BoundedContextRequest::new(
+                BoundedContextSystem::new(options.system_name.clone(), entities),
+                options.similarity == SimilarityMetric::WuPalmer,
+            );
+            let mut merged = retrieve(&req, client).await?;
+            attribute_sources(&mut merged, entities, options.threshold);
+            merged
+        }
+        MergeStrategy::Native => native::merge(&options.system_name, entities, options.threshold),
+    };
+    let entities: Vec<Entity> = merged.into();
     match EntityGraph::try_new(&entities) {
         Some(graph) => Ok(graph),
         None => Err(Error::Conversion),
     }
 }
 
-/// Make the API call to merge entities
-async fn retrieve(req: BoundedContextRequest) -> Result<MergedEntitySystem, Error> {
-    let client = Client::default();
-    let options = get_opts();
+/// Attribute every entity to the merged entity with the most similar name,
+/// since the external service does not return what it merged. An entity with
+/// no merged entity at least as similar as the threshold is left unattributed
+fn attribute_sources(merged: &mut MergedEntitySystem, entities: &[Entity], threshold: f64) {
+    let merged = merged.entities_mut();
+    for entity in entities {
+        let best = merged
+            .iter()
+            .enumerate()
+            .map(|(i, candidate)| (i, native::name_similarity(candidate.name(), &entity.name)))
+            .filter(|(_, similarity)| *similarity >= threshold)
+            // Prefer the first of the equally similar entities
+            .rev()
+            .max_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(i, _)| i);
+        if let Some(best) = best {
+            merged[best].sources.extend(entity.source_entities());
+        }
+    }
+}
+
+/// Make the API call to merge entities, retrying failed calls with an exponential
+/// backoff as configured
+async fn retrieve(
+    req: &BoundedContextRequest,
+    config: &BoundedContextClient,
+) -> Result<MergedEntitySystem, Error> {
+    let client = config.client()?;
+    let mut retry = 0;
+    loop {
+        config.check_circuit()?;
+        let result = try_retrieve(req, &client, config).await;
+        match result {
+            Err(err) => {
+                config.record(!err.is_unavailable());
+                if !err.is_retryable() || retry >= config.retries {
+                    return Err(err.into());
+                }
+            }
+            Ok(merged) => {
+                config.record(true);
+                return Ok(merged);
+            }
+        }
+
+        actix_web::rt::time::delay_for(config.backoff(retry)).await;
+        retry += 1;
+    }
+}
 
+/// Make a single API call to merge entities
+async fn try_retrieve(
+    req: &BoundedContextRequest,
+    client: &Client,
+    config: &BoundedContextClient,
+) -> Result<MergedEntitySystem, RemoteCallError> {
     // Make request and handle error (if occurred)
-    let result = client
-        .post(format!("http://{}:{}/", options.host, options.port))
+    let mut result = client
+        .post(&config.base_url)
         .header("User-Agent", "actix-web/3.0")
-        .send_json(&req)
-        .await
-        .map_err(|err| Error::RemoteCall(err.to_string()))?;
-
-    // Handle error response status
-    let mut result = match result.status() {
-        StatusCode::OK => Ok(result),
-        err => Err(Error::RemoteCall(err.to_string())),
-    }?;
+        .send_json(req)
+        .await?;
 
     // Handle errors from extracting body
-    let body = match result.body().await {
-        Ok(result) => Ok(result),
-        Err(err) => Err(Error::RemoteCall(err.to_string())),
-    }?;
+    let body = result.body().limit(config.max_response_bytes).await?;
+
+    // Handle error response status
+    if result.status() != StatusCode::OK {
+        return Err(RemoteCallError::Status {
+            status: result.status().as_u16(),
+            body: String::from_utf8_lossy(&body).into_owned(),
+        });
+    }
 
     // Decode and return
-    let body = serde_json::from_slice::<'_, MergedEntitySystem>(&body)
-        .map_err(|err| Error::Deserialize(err.to_string()))?;
-    Ok(body)
+    serde_json::from_slice::<'_, MergedEntitySystem>(&body).map_err(|err| RemoteCallError::Decode {
+        message: err.to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use prophet_model::DatabaseType;
+
+    use super::*;
+
+    #[test]
+    fn attribute_sources_above_threshold() {
+        let merged_entity = |name: &str| {
+            MergedEntity::new(MergedName::new(name.to_string(), name.to_string()), vec![])
+        };
+        let mut merged = MergedEntitySystem::new(
+            "test".into(),
+            vec![merged_entity("Order"), merged_entity("Customer")],
+        );
+        let entities = [
+            Entity::new("Orders", vec![], DatabaseType::MySQL),
+            Entity::new("Customer", vec![], DatabaseType::MongoDB),
+            Entity::new("Shipment", vec![], DatabaseType::MySQL),
+        ];
+
+        attribute_sources(&mut merged, &entities, 0.8);
+        let sources: Vec<Vec<_>> = merged
+            .entities_mut()
+            .iter()
+            .map(|entity| {
+                entity
+                    .sources
+                    .iter()
+                    .map(|source| source.entity.as_str())
+                    .collect()
+            })
+            .collect();
+        assert_eq!(vec![vec!["Orders"], vec!["Customer"]], sources);
+    }
 }
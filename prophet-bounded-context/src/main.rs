@@ -1,45 +1,35 @@
+use prophet_bounded_context::{BoundedContextClient, BoundedContextOptions};
 use prophet_model::{DatabaseType, Entity, EntityGraph, Field};
 
 /// TODO replace with a proper integration test
 #[actix_web::main]
 async fn main() {
-    let entity_a = Entity {
-        name: "Entity1".to_string(),
-        fields: vec![Field {
-            name: "FieldA".to_string(),
-            ty: "Foo".to_string(),
-            is_collection: false,
-        }],
-        ty: DatabaseType::MongoDB,
-    };
-    let entity_b = Entity {
-        name: "AnotherEntity".to_string(),
-        fields: vec![Field {
-            name: "AnotherField".to_string(),
-            ty: "Waa".to_string(),
-            is_collection: true,
-        }],
-        ty: DatabaseType::MySQL,
-    };
+    let entity_a = Entity::new(
+        "Entity1",
+        vec![Field::new("FieldA", "Foo", false)],
+        DatabaseType::MongoDB,
+    );
+    let entity_b = Entity::new(
+        "AnotherEntity",
+        vec![Field::new("AnotherField", "Waa", true)],
+        DatabaseType::MySQL,
+    );
 
-    let oracle = match EntityGraph::try_new(&[
-        Entity {
-            ty: DatabaseType::Unknown("".to_string()),
-            ..entity_a.clone()
-        },
-        Entity {
-            ty: DatabaseType::Unknown("".to_string()),
-            ..entity_b.clone()
-        },
-    ]) {
+    // The merged entities keep the database types of the entities they were merged from
+    let oracle = match EntityGraph::try_new(&[entity_a.clone(), entity_b.clone()]) {
         Some(graph) => graph,
         None => panic!("Cannot convert oracle entities"),
     };
     let ms = &[entity_a.clone(), entity_a, entity_b];
 
-    let result = prophet_bounded_context::get_bounded_context(ms)
-        .await
-        .unwrap();
+    let client = BoundedContextClient::from_env().unwrap();
+    let result = prophet_bounded_context::get_bounded_context(
+        ms,
+        &BoundedContextOptions::default(),
+        &client,
+    )
+    .await
+    .unwrap();
     println!("Expected: {:#?}", oracle);
     println!("Result: {:#?}", result);
 }
@@ -0,0 +1,255 @@
+use std::collections::HashMap;
+
+use prophet_model::{most_common, normalize, Entity, Field, TypeExpr};
+
+use crate::compat::*;
+
+/// How much the name similarity weighs against the field similarity of two entities
+const NAME_WEIGHT: f64 = 0.6;
+
+/// Merge the entities whose names and fields are similar, producing the same merged
+/// system as the external bounded context service
+pub(crate) fn merge(system_name: &str, entities: &[Entity], threshold: f64) -> MergedEntitySystem {
+    // Group similar entities into clusters, keeping the clusters in the order
+    // their first entity appears in
+    let mut parents: Vec<usize> = (0..entities.len()).collect();
+    for i in 0..entities.len() {
+        for j in (i + 1)..entities.len() {
+            if entity_similarity(&entities[i], &entities[j]) >= threshold {
+                union(&mut parents, i, j);
+            }
+        }
+    }
+
+    let mut clusters: Vec<Vec<&Entity>> = vec![];
+    let mut cluster_ndx: HashMap<usize, usize> = HashMap::new();
+    for (i, entity) in entities.iter().enumerate() {
+        let root = find(&mut parents, i);
+        let ndx = *cluster_ndx.entry(root).or_insert_with(|| {
+            clusters.push(vec![]);
+            clusters.len() - 1
+        });
+        clusters[ndx].push(entity);
+    }
+
+    // Name each merged entity after the most common name in its cluster, and
+    // remember which merged entity every original entity name now refers to
+    let names: Vec<String> = clusters
+        .iter()
+        .map(|cluster| {
+            most_common(cluster.iter().map(|entity| entity.name.as_str()))
+                .unwrap_or_default()
+                .to_string()
+        })
+        .collect();
+    let renamed: HashMap<String, &str> = clusters
+        .iter()
+        .zip(names.iter())
+        .flat_map(|(cluster, name)| {
+            cluster
+                .iter()
+                .map(move |entity| (normalize(&entity.name), name.as_str()))
+        })
+        .collect();
+
+    let merged = clusters
+        .iter()
+        .zip(names.iter())
+        .map(|(cluster, name)| merge_cluster(cluster, name, &renamed))
+        .collect();
+    MergedEntitySystem::new(system_name.to_string(), merged)
+}
+
+/// Merge the fields of a cluster of similar entities into one entity
+fn merge_cluster(cluster: &[&Entity], name: &str, renamed: &HashMap<String, &str>) -> MergedEntity {
+    // Fields with the same normalized name are the same field
+    let mut groups: Vec<(String, Vec<&Field>)> = vec![];
+    for field in cluster.iter().flat_map(|entity| &entity.fields) {
+        let key = normalize(&field.name);
+        match groups.iter_mut().find(|(group_key, _)| *group_key == key) {
+            Some((_, group)) => group.push(field),
+            None => groups.push((key, vec![field])),
+        }
+    }
+
+    let fields = groups
+        .into_iter()
+        .map(|(_, group)| {
+            let field_name = most_common(group.iter().map(|field| field.name.as_str()))
+                .unwrap_or_default()
+                .to_string();
+            let ty = most_common(group.iter().map(|field| field.ty.as_str()))
+                .unwrap_or_default()
+                .to_string();
+            let mut collection = group.iter().any(|field| field.is_collection);
+
+            // Fields referring to an entity, such as through `List<Order>` or
+            // `Order | None`, now refer to the entity it was merged into
+            let referred = TypeExpr::parse(&ty);
+            let (ty, reference) = match renamed.get(&normalize(&referred.name)) {
+                Some(entity_name) => {
+                    collection |= referred.is_collection;
+                    (entity_name.to_string(), true)
+                }
+                None => (ty, false),
+            };
+            let mut merged = MergedField::new(
+                MergedName::new(field_name.clone(), field_name),
+                ty,
+                reference,
+                collection,
+            );
+            merged.sources = group.into_iter().cloned().collect();
+            merged
+        })
+        .collect();
+
+    let mut merged = MergedEntity::new(MergedName::new(name.to_string(), name.to_string()), fields);
+    merged.sources = cluster
+        .iter()
+        .flat_map(|entity| entity.source_entities())
+        .collect();
+    merged
+}
+
+/// The similarity of two entities by their names and field names, from 0 to 1
+fn entity_similarity(a: &Entity, b: &Entity) -> f64 {
+    let name_similarity = name_similarity(&a.name, &b.name);
+    if name_similarity >= 1.0 {
+        return 1.0;
+    }
+
+    let a_fields: Vec<_> = a
+        .fields
+        .iter()
+        .map(|field| normalize(&field.name))
+        .collect();
+    let b_fields: Vec<_> = b
+        .fields
+        .iter()
+        .map(|field| normalize(&field.name))
+        .collect();
+    let shared = a_fields
+        .iter()
+        .filter(|field| b_fields.contains(field))
+        .count();
+    let total = a_fields.len() + b_fields.len() - shared;
+    let field_similarity = if total == 0 {
+        0.0
+    } else {
+        shared as f64 / total as f64
+    };
+
+    NAME_WEIGHT * name_similarity + (1.0 - NAME_WEIGHT) * field_similarity
+}
+
+/// The normalized Levenshtein similarity of two names, from 0 to 1
+pub(crate) fn name_similarity(a: &str, b: &str) -> f64 {
+    let (a, b) = (normalize(a), normalize(b));
+    let longest = a.chars().count().max(b.chars().count());
+    if longest == 0 {
+        return 0.0;
+    }
+    1.0 - levenshtein(&a, &b) as f64 / longest as f64
+}
+
+/// The number of single character edits to turn `a` into `b`
+fn levenshtein(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, a_char) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a_char != *b_char);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+fn find(parents: &mut [usize], i: usize) -> usize {
+    let mut root = i;
+    while parents[root] != root {
+        root = parents[root];
+    }
+    parents[i] = root;
+    root
+}
+
+fn union(parents: &mut [usize], a: usize, b: usize) {
+    let (a, b) = (find(parents, a), find(parents, b));
+    // Keep the earliest entity as the root of the cluster
+    parents[a.max(b)] = a.min(b);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::BoundedContextOptions;
+    use prophet_model::DatabaseType;
+
+    #[test]
+    fn merge_similar_entities() {
+        let entities = [
+            Entity::new(
+                "Order",
+                vec![
+                    Field::new("id", "String", false),
+                    Field::new("items", "OrderItem", true),
+                ],
+                DatabaseType::MySQL,
+            ),
+            Entity::new(
+                "Order",
+                vec![
+                    Field::new("id", "String", false),
+                    Field::new("price", "double", false),
+                ],
+                DatabaseType::MongoDB,
+            ),
+            Entity::new(
+                "Customer",
+                vec![
+                    Field::new("id", "String", false),
+                    Field::new("name", "String", false),
+                    Field::new("orders", "List<com.shop.Order>", false),
+                ],
+                DatabaseType::MySQL,
+            ),
+            Entity::new(
+                "Customers",
+                vec![
+                    Field::new("id", "String", false),
+                    Field::new("name", "String", false),
+                    Field::new("orders", "List<com.shop.Order>", false),
+                ],
+                DatabaseType::MongoDB,
+            ),
+            Entity::new(
+                "order_item",
+                vec![Field::new("count", "int", false)],
+                DatabaseType::MySQL,
+            ),
+        ];
+
+        let threshold = BoundedContextOptions::default().threshold;
+        let merged: Vec<Entity> = merge("test", &entities, threshold).into();
+        let names: Vec<_> = merged.iter().map(|entity| entity.name.as_str()).collect();
+        assert_eq!(vec!["Order", "Customer", "order_item"], names);
+
+        let order_fields: Vec<_> = merged[0].fields.iter().map(|f| f.name.as_str()).collect();
+        assert_eq!(vec!["id", "items", "price"], order_fields);
+        // References are renamed to the entity they were merged into
+        assert_eq!(Field::new("items", "order_item", true), merged[0].fields[1]);
+        assert_eq!(Field::new("orders", "Order", true), merged[1].fields[2]);
+        // The merged entities remember what they were merged from
+        assert_eq!(2, merged[0].sources.len());
+        assert_eq!(
+            vec![&DatabaseType::MySQL, &DatabaseType::MongoDB],
+            merged[0].conflicting_types()
+        );
+        assert_eq!(DatabaseType::MySQL, merged[0].ty);
+    }
+}
@@ -0,0 +1,144 @@
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::Error;
+
+/// How the entities of all microservices are merged into a bounded context
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MergeStrategy {
+    /// Merge the entities with the external bounded context service
+    #[default]
+    Remote,
+    /// Merge the entities in-process, by the similarity of their names and fields
+    Native,
+}
+
+impl FromStr for MergeStrategy {
+    type Err = String;
+
+    fn from_str(strategy: &str) -> Result<Self, Self::Err> {
+        match strategy {
+            "remote" => Ok(MergeStrategy::Remote),
+            "native" => Ok(MergeStrategy::Native),
+            _ => Err(format!("Unknown merge strategy: {}", strategy)),
+        }
+    }
+}
+
+/// How the similarity of the names of two entities is measured
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SimilarityMetric {
+    /// Compare the spelling of the names
+    #[default]
+    Syntactic,
+    /// Compare the meaning of the words in the names with the Wu-Palmer
+    /// similarity, which is only supported by the remote merge strategy
+    WuPalmer,
+}
+
+/// The options for merging the entities of all microservices into a bounded context
+///
+/// The serialized representation in JSON is as follows, where every field is optional
+/// ```json
+/// {
+///   "strategy": "native",
+///   "similarity": "syntactic",
+///   "threshold": 0.8,
+///   "system_name": "trainticket"
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BoundedContextOptions {
+    /// How the entities are merged
+    pub strategy: MergeStrategy,
+    /// How the similarity of entity names is measured
+    pub similarity: SimilarityMetric,
+    /// The minimum similarity, from 0 to 1, for two entities with different names to be
+    /// merged. The remote service has its own threshold, so with the remote strategy it
+    /// is the minimum similarity for an entity to be attributed to a merged entity
+    pub threshold: f64,
+    /// The name of the analyzed system
+    pub system_name: String,
+}
+
+impl Default for BoundedContextOptions {
+    fn default() -> Self {
+        BoundedContextOptions {
+            strategy: MergeStrategy::default(),
+            similarity: SimilarityMetric::default(),
+            threshold: 0.8,
+            system_name: "system".into(),
+        }
+    }
+}
+
+/// The options a request overrides the configured [`BoundedContextOptions`] with,
+/// where any field left out keeps its configured value
+///
+/// The serialized representation in JSON is the same as for [`BoundedContextOptions`]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BoundedContextOverrides {
+    pub strategy: Option<MergeStrategy>,
+    pub similarity: Option<SimilarityMetric>,
+    pub threshold: Option<f64>,
+    pub system_name: Option<String>,
+}
+
+impl BoundedContextOptions {
+    /// Overrides these options with those provided, keeping any that are not
+    pub fn with_overrides(&self, overrides: BoundedContextOverrides) -> Self {
+        BoundedContextOptions {
+            strategy: overrides.strategy.unwrap_or(self.strategy),
+            similarity: overrides.similarity.unwrap_or(self.similarity),
+            threshold: overrides.threshold.unwrap_or(self.threshold),
+            system_name: overrides
+                .system_name
+                .unwrap_or_else(|| self.system_name.clone()),
+        }
+    }
+
+    /// Ensures the options are supported by the chosen strategy
+    pub fn validate(&self) -> Result<(), Error> {
+        if !(0.0..=1.0).contains(&self.threshold) {
+            return Err(Error::InvalidOptions(format!(
+                "Threshold {} is not between 0 and 1",
+                self.threshold
+            )));
+        }
+        if self.strategy == MergeStrategy::Native && self.similarity == SimilarityMetric::WuPalmer {
+            return Err(Error::InvalidOptions(
+                "The Wu-Palmer similarity is only supported by the remote strategy".into(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn override_provided_options() {
+        let configured = BoundedContextOptions {
+            strategy: MergeStrategy::Native,
+            system_name: "trainticket".into(),
+            ..BoundedContextOptions::default()
+        };
+        let overrides: BoundedContextOverrides =
+            serde_json::from_str(r#"{ "threshold": 0.9 }"#).unwrap();
+
+        assert_eq!(
+            configured.with_overrides(overrides),
+            BoundedContextOptions {
+                threshold: 0.9,
+                ..configured.clone()
+            }
+        );
+    }
+}
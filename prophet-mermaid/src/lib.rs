@@ -1,15 +1,32 @@
 use prophet_model::{
-    Cardinality, Edge, Edges, Entity, EntityGraph, Microservice, MicroserviceCall,
-    MicroserviceGraph,
+    CallType, Cardinality, DatabaseCategory, DatabaseType, Edge, Edges, Entity, EntityGraph,
+    Microservice, MicroserviceCall, MicroserviceGraph, Relationship, Severity, TypeExpr,
 };
-use serde::Serialize;
-use std::fmt::Write;
+use serde::{Deserialize, Serialize};
+use std::{collections::HashSet, fmt::Write};
+
+/// The kind of diagram to render entities as
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EntityDiagramStyle {
+    /// A class diagram with the database of every entity
+    #[default]
+    Class,
+    /// An entity relationship diagram with crow's foot cardinalities and keys
+    Er,
+}
 
 /// A mermaid string to represent a microservice communication diagram
 /// or an entity diagram
 #[derive(Debug, Default, PartialEq, Eq, Serialize)]
 pub struct MermaidString(String);
 
+impl From<MermaidString> for String {
+    fn from(mermaid: MermaidString) -> Self {
+        mermaid.0
+    }
+}
+
 impl MermaidString {
     fn from_graph<N, E, FNode, FEdge>(
         nodes: Vec<N>,
@@ -53,11 +70,26 @@ impl MermaidString {
 
         Self(mermaid)
     }
+
+    /// Highlights the nodes with the provided names, colored by the severity of
+    /// the anti-patterns they are part of
+    pub fn highlight<'a>(mut self, nodes: impl IntoIterator<Item = (&'a str, Severity)>) -> Self {
+        for (name, severity) in nodes {
+            let (fill, stroke) = match severity {
+                Severity::High => ("#f8d7da", "#c0392b"),
+                Severity::Medium => ("#fdebd0", "#e67e22"),
+                Severity::Low => ("#fcf3cf", "#b7950b"),
+            };
+            writeln!(self.0, "style {} fill:{},stroke:{}", name, fill, stroke).unwrap();
+        }
+        self
+    }
 }
 
 /*
 graph TD
 SourceMicroservice -->|"HTTP Verb: GET<br/>Arguments: ...<br/>Endpoint function ..."| TargetMicroservice
+Producer -.->|"Kafka topic: orders"| Consumer
 ...
  */
 impl From<MicroserviceGraph> for MermaidString {
@@ -66,38 +98,74 @@ impl From<MicroserviceGraph> for MermaidString {
             w: &mut impl Write,
             from: &str,
             to: &str,
-            label: Option<&str>,
+            arrow: &str,
+            label: &str,
         ) -> std::fmt::Result {
-            // Write the call edge with any extra information if available
-            match label {
-                Some(label) => writeln!(w, "{} -->|\"{}\"| {}", to, label, from),
-                None => writeln!(w, "{} --> {}", to, from),
-            }
+            // Write the call edge with its extra information
+            writeln!(w, "{} {}|\"{}\"| {}", from, arrow, label, to)
         }
 
         fn write_ms_edge(
             w: &mut impl Write,
             edge: &Edge<Microservice, MicroserviceCall>,
         ) -> std::fmt::Result {
-            // Note: it looks like the only information we have defined for the calls at the moment
-            // is just the HTTP method or RPC call indicator. As seen in the comment above, there
-            // was previously extra information like arguments and specific endpoints.
-            let label = match &edge.weight {
-                call_ty @ MicroserviceCall::Http(_) => {
-                    format!("HTTP Verb: {}", call_ty)
+            let call = &edge.weight;
+            let mut lines = vec![match &call.ty {
+                CallType::Http(method) => format!("HTTP Verb: {}", method),
+                call_ty @ CallType::Rpc => format!("{}", call_ty),
+                CallType::Messaging { broker, topic, .. } => {
+                    format!("{} topic: {}", broker, topic)
                 }
-                call_ty @ MicroserviceCall::Rpc => format!("{}", call_ty),
+            }];
+            if let Some(endpoint) = &call.endpoint {
+                lines.push(format!("Endpoint: {}", endpoint));
+            }
+            // Prefer what the call was resolved to over what the call site says
+            let endpoint_function = call
+                .target
+                .as_ref()
+                .and_then(|target| target.handler.as_ref())
+                .or(call.endpoint_function.as_ref());
+            if let Some(endpoint_function) = endpoint_function {
+                lines.push(format!("Endpoint function: {}", endpoint_function));
+            }
+            if let Some(target) = &call.target {
+                lines.push(format!("Handled by: {} {}", target.method, target.path));
+            }
+            if !call.arguments.is_empty() {
+                lines.push(format!("Arguments: {}", call.arguments.join(", ")));
+            }
+            if let Some(return_type) = &call.return_type {
+                lines.push(format!("Returns: {}", return_type));
+            }
+            if let Some(calling_method) = &call.calling_method {
+                lines.push(format!("Called from: {}", calling_method));
+            }
+            if let Some(location) = &call.location {
+                lines.push(format!("At: {}", location));
+            }
+            let label = lines
+                .iter()
+                .map(|line| escape_label(line))
+                .collect::<Vec<_>>()
+                .join("<br/>");
+            // Asynchronous messages are drawn with dotted arrows
+            let arrow = match call.ty {
+                CallType::Messaging { .. } => "-.->",
+                _ => "-->",
             };
             write_edge(
                 w,
                 edge.from.name.as_str(),
                 edge.to.name.as_str(),
-                Some(label.as_str()),
+                arrow,
+                &label,
             )
         }
 
         fn write_ms_orphan(w: &mut impl Write, node: &Microservice) -> std::fmt::Result {
-            write_edge(w, node.name.as_str(), "N/A", None)
+            // A microservice without calls is drawn on its own
+            writeln!(w, "{}", node.name)
         }
 
         MermaidString::from_graph(
@@ -111,22 +179,41 @@ impl From<MicroserviceGraph> for MermaidString {
     }
 }
 
+/// Escapes the characters of an edge label that mermaid would otherwise parse
+fn escape_label(label: &str) -> String {
+    label
+        .replace('"', "#quot;")
+        .replace('<', "#lt;")
+        .replace('>', "#gt;")
+}
+
 /*
 classDiagram
 class A {
-<<db_type_annotation>>
+<<db_type (db_category)>>
 +Type name
 ...
 }
-A "1" --> "*" B
+A "1" -- "*" B
+C "*" --> "0..1" A
 ...
  */
 impl From<EntityGraph> for MermaidString {
     fn from(graph: EntityGraph) -> Self {
         fn write_entity_string(w: &mut impl Write, entity: &Entity) -> std::fmt::Result {
             writeln!(w, "class {} {{", entity.name)?;
-            // Write the database type
-            writeln!(w, "<<{}>>", entity.ty)?;
+            // Write the database type and its category, if they are known, or all
+            // of the databases the merged entities were stored in
+            let conflicting_types = entity.conflicting_types();
+            match (&entity.ty, entity.ty.category()) {
+                _ if !conflicting_types.is_empty() => {
+                    let types: Vec<_> = conflicting_types.iter().map(|ty| ty.to_string()).collect();
+                    writeln!(w, "<<Conflict: {}>>", types.join(", "))?
+                }
+                (DatabaseType::Unknown(ty), _) if ty.is_empty() => (),
+                (ty, DatabaseCategory::Unknown) => writeln!(w, "<<{}>>", ty)?,
+                (ty, category) => writeln!(w, "<<{} ({})>>", ty, category)?,
+            }
             // Write the fields
             for field in entity.fields.iter() {
                 let ty = if field.is_collection {
@@ -141,14 +228,24 @@ impl From<EntityGraph> for MermaidString {
 
         fn write_entity_edge(
             w: &mut impl Write,
-            edge: &Edge<Entity, Cardinality>,
+            edge: &Edge<Entity, Relationship>,
         ) -> std::fmt::Result {
-            // Write the relation represented by the edge
-            let cardinality = edge.weight.to_string();
+            // Write the relation represented by the edge, which can only be
+            // navigated from its source unless it is bidirectional
+            let relationship = &edge.weight;
+            let link = if relationship.is_bidirectional() {
+                "--"
+            } else {
+                "-->"
+            };
             writeln!(
                 w,
-                r#"{} "1" --> "{}" {}"#,
-                edge.from.name, cardinality, edge.to.name
+                r#"{} "{}" {} "{}" {}"#,
+                edge.from.name,
+                relationship.source.to_string(),
+                link,
+                relationship.target.to_string(),
+                edge.to.name
             )
         }
 
@@ -163,24 +260,139 @@ impl From<EntityGraph> for MermaidString {
     }
 }
 
+impl MermaidString {
+    /// Renders an entity graph as a diagram of the provided style
+    pub fn from_entity_graph(graph: EntityGraph, style: EntityDiagramStyle) -> Self {
+        match style {
+            EntityDiagramStyle::Class => MermaidString::from(graph),
+            EntityDiagramStyle::Er => MermaidString::er_diagram(graph),
+        }
+    }
+
+    /*
+    erDiagram
+    A {
+    Long id PK
+    B b FK
+    C[] cs
+    }
+    A }o--|| B : "b"
+    ...
+     */
+    /// Renders an entity graph as an entity relationship diagram
+    pub fn er_diagram(graph: EntityGraph) -> Self {
+        // The fields referring to a single other entity are foreign keys
+        let mut foreign_keys = HashSet::new();
+        for edge in graph.edges().into_inner() {
+            let relationship = edge.weight;
+            if !relationship.target.is_many() {
+                foreign_keys.insert((edge.from.name.clone(), relationship.field));
+            }
+            if let Some(inverse_field) = relationship.inverse_field {
+                if !relationship.source.is_many() {
+                    foreign_keys.insert((edge.to.name, inverse_field));
+                }
+            }
+        }
+
+        let write_er_entity = |w: &mut String, entity: &Entity| -> std::fmt::Result {
+            writeln!(w, "{} {{", entity.name)?;
+            for field in entity.fields.iter() {
+                // Mermaid only allows plain words as attribute types
+                let ty = TypeExpr::parse(&field.ty);
+                let ty = if field.is_collection || ty.is_collection {
+                    format!("{}[]", ty.name)
+                } else {
+                    ty.name
+                };
+                let mut keys = vec![];
+                if field.is_id {
+                    keys.push("PK");
+                }
+                if foreign_keys.contains(&(entity.name.clone(), field.name.clone())) {
+                    keys.push("FK");
+                }
+                if field.is_unique && !field.is_id {
+                    keys.push("UK");
+                }
+                write!(w, "{} {}", attribute_word(&ty), attribute_word(&field.name))?;
+                if !keys.is_empty() {
+                    write!(w, " {}", keys.join(", "))?;
+                }
+                writeln!(w)?;
+            }
+            writeln!(w, "}}")
+        };
+
+        fn write_er_edge(w: &mut String, edge: &Edge<Entity, Relationship>) -> std::fmt::Result {
+            use Cardinality::*;
+
+            let relationship = &edge.weight;
+            let source = match relationship.source {
+                ZeroOrOne => "|o",
+                One => "||",
+                Many => "}o",
+                OneOrMany => "}|",
+            };
+            let target = match relationship.target {
+                ZeroOrOne => "o|",
+                One => "||",
+                Many => "o{",
+                OneOrMany => "|{",
+            };
+            let label = match &relationship.inverse_field {
+                Some(inverse_field) => format!("{} / {}", relationship.field, inverse_field),
+                None => relationship.field.clone(),
+            };
+            writeln!(
+                w,
+                r#"{} {}--{} {} : "{}""#,
+                edge.from.name,
+                source,
+                target,
+                edge.to.name,
+                escape_label(&label)
+            )
+        }
+
+        MermaidString::from_graph(
+            graph.nodes(),
+            graph.edges(),
+            "erDiagram",
+            Some(write_er_entity),
+            write_er_edge,
+            None,
+        )
+    }
+}
+
+/// Replaces the characters mermaid does not allow in the types and names of attributes
+fn attribute_word(word: &str) -> String {
+    word.chars()
+        .map(|c| match c {
+            c if c.is_alphanumeric() || "_-[]()".contains(c) => c,
+            _ => '_',
+        })
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use prophet_model::*;
+    use prophet_model::{testing::microservice_graph, *};
     use test_case::test_case;
 
     const ENTITY_MERMAID: &str = r#"classDiagram
 class EntityOne {
-<<MySQL>>
+<<MySQL (Relational)>>
 +List<EntityTwo> f1
 }
 class EntityTwo {
-<<MySQL>>
+<<MySQL (Relational)>>
 +int x
 +EntityOne other
 }
-EntityOne "1" --> "*" EntityTwo
-EntityTwo "1" --> "1" EntityOne
+EntityOne "1" -- "*" EntityTwo
 "#;
 
     fn get_entity_graph() -> EntityGraph {
@@ -202,7 +414,127 @@ EntityTwo "1" --> "1" EntityOne
         .unwrap()
     }
 
+    const UNIDIRECTIONAL_MERMAID: &str = r#"classDiagram
+class Order {
+<<MongoDB (Document)>>
++Optional<Customer> customer
+}
+class Customer {
+<<MongoDB (Document)>>
+}
+Order "*" --> "0..1" Customer
+"#;
+
+    fn get_unidirectional_graph() -> EntityGraph {
+        EntityGraph::try_new(&[
+            Entity::new(
+                "Order",
+                vec![Field::new("customer", "Optional<Customer>", false)],
+                DatabaseType::MongoDB,
+            ),
+            Entity::new("Customer", vec![], DatabaseType::MongoDB),
+        ])
+        .unwrap()
+    }
+
+    const FOREIGN_KEY_MERMAID: &str = r#"classDiagram
+class Payment {
+<<PostgreSQL (Relational)>>
++Long orderId
+}
+class Order {
+<<PostgreSQL (Relational)>>
+}
+Payment "*" --> "1" Order
+"#;
+
+    fn get_foreign_key_graph() -> EntityGraph {
+        EntityGraph::try_new(&[
+            Entity::new(
+                "Payment",
+                vec![Field::new("orderId", "Long", false).with_annotations(vec![
+                    "@ManyToOne".into(),
+                    "@JoinColumn(name = \"order_id\")".into(),
+                ])],
+                DatabaseType::PostgreSQL,
+            ),
+            Entity::new("Order", vec![], DatabaseType::PostgreSQL),
+        ])
+        .unwrap()
+    }
+
+    const ER_MERMAID: &str = r#"erDiagram
+EntityOne {
+EntityTwo[] f1
+}
+EntityTwo {
+int x
+EntityOne other FK
+}
+Payment {
+Long id PK
+Long orderId FK
+String reference UK
+}
+Order {
+}
+EntityOne ||--o{ EntityTwo : "f1 / other"
+Payment }o--|| Order : "orderId"
+"#;
+
+    #[test]
+    fn er_diagram_test() {
+        let mut entities = get_entity_graph().nodes();
+        entities.push(Entity::new(
+            "Payment",
+            vec![
+                Field::new("id", "Long", false).with_annotations(vec!["@Id".into()]),
+                Field::new("orderId", "Long", false).with_annotations(vec!["@ManyToOne".into()]),
+                Field::new("reference", "String", false)
+                    .with_annotations(vec!["@Column(unique = true)".into()]),
+            ],
+            DatabaseType::PostgreSQL,
+        ));
+        entities.push(Entity::new("Order", vec![], DatabaseType::PostgreSQL));
+        let graph = EntityGraph::try_new(&entities).unwrap();
+
+        assert_eq!(
+            MermaidString::from_entity_graph(graph, EntityDiagramStyle::Er),
+            MermaidString(ER_MERMAID.to_string())
+        );
+    }
+
+    const MESSAGING_MERMAID: &str = r#"graph TD
+orders -.->|"Kafka topic: orders"| shipping
+payments
+"#;
+
+    fn get_messaging_graph() -> MicroserviceGraph {
+        let call = MicroserviceCall::new(CallType::Messaging {
+            broker: Broker::Kafka,
+            topic: "orders".into(),
+            role: MessagingRole::Producer,
+        });
+        microservice_graph(&["orders", "shipping", "payments"], [(0, 1, call)])
+    }
+
+    const HTTP_MERMAID: &str = r#"graph TD
+orders -->|"HTTP Verb: GET<br/>Endpoint: /users/{id}<br/>Arguments: Long"| users
+"#;
+
+    fn get_http_graph() -> MicroserviceGraph {
+        let ty = serde_json::from_value(serde_json::json!({ "http": "GET" })).unwrap();
+        let mut call = MicroserviceCall::new(ty);
+        call.endpoint = Some("/users/{id}".into());
+        call.arguments = vec!["Long".into()];
+        microservice_graph(&["orders", "users"], [(0, 1, call)])
+    }
+
+    #[test_case(get_http_graph() => MermaidString(HTTP_MERMAID.to_string()) ; "http")]
+    #[test_case(get_messaging_graph() => MermaidString(MESSAGING_MERMAID.to_string()) ; "messaging")]
     #[test_case(get_entity_graph() => MermaidString(ENTITY_MERMAID.to_string()) ; "one_to_many")]
+    #[test_case(get_unidirectional_graph() => MermaidString(UNIDIRECTIONAL_MERMAID.to_string()) ; "unidirectional")]
+    #[test_case(get_foreign_key_graph() => MermaidString(FOREIGN_KEY_MERMAID.to_string()) ; "foreign_key")]
     fn from_entity_graph_test(graph: impl Into<MermaidString>) -> MermaidString {
         graph.into()
     }
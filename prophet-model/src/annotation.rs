@@ -0,0 +1,397 @@
+use std::collections::BTreeMap;
+
+use crate::{normalize, split_top_level, Cardinality, Field, TypeExpr};
+
+/// Annotations marking the field identifying its entity
+const ID_ANNOTATIONS: &[&str] = &[
+    "id",
+    "embeddedid",
+    "mongoid",
+    "primarykey",
+    "primarycolumn",
+    "primarygeneratedcolumn",
+    "objectidcolumn",
+];
+
+/// Annotations marking a field that can never be empty
+const NOT_NULL_ANNOTATIONS: &[&str] = &["notnull", "nonnull", "notblank", "notempty"];
+
+/// Annotations referring to another entity without saying how many
+const REFERENCE_ANNOTATIONS: &[&str] = &[
+    "dbref",
+    "documentreference",
+    "reference",
+    "joincolumn",
+    "relationship",
+    "relation",
+];
+
+/// Annotations naming a column rather than the entity referred to
+const COLUMN_ANNOTATIONS: &[&str] = &["joincolumn"];
+
+/// An annotation or decorator of a field, such as `@Column(name = "id", nullable = false)`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Annotation {
+    /// The unqualified name, such as `Column`
+    pub name: String,
+    /// The arguments by name, where the first positional argument is named `value`
+    /// and any others are named by their position
+    pub arguments: BTreeMap<String, String>,
+}
+
+impl Annotation {
+    /// Parses a Java annotation, a TypeScript or Python decorator, or a call
+    /// assigned to a field like `ForeignKey(Order, on_delete=CASCADE)`
+    pub fn parse(annotation: &str) -> Option<Self> {
+        let annotation = annotation.trim().trim_start_matches('@');
+        let (name, arguments) = match annotation.split_once('(') {
+            Some((name, arguments)) => (name, arguments.trim_end().strip_suffix(')')?),
+            None => (annotation, ""),
+        };
+        let name = name.trim().rsplit('.').next()?;
+        if name.is_empty() || !name.chars().all(|c| c.is_alphanumeric() || c == '_') {
+            return None;
+        }
+
+        let mut parsed = BTreeMap::new();
+        let mut position = 0;
+        for argument in split_top_level(arguments, ',') {
+            // Options objects, such as `{ nullable: true }`
+            if let Some(options) = argument
+                .strip_prefix('{')
+                .and_then(|options| options.strip_suffix('}'))
+            {
+                for option in split_top_level(options, ',') {
+                    if let Some((key, value)) = assignment(option, ':') {
+                        parsed.insert(key, value);
+                    }
+                }
+                continue;
+            }
+            match assignment(argument, '=') {
+                Some((key, value)) => {
+                    parsed.insert(key, value);
+                }
+                None if !argument.is_empty() => {
+                    let key = match position {
+                        0 => "value".to_string(),
+                        _ => position.to_string(),
+                    };
+                    parsed.insert(key, unquote(argument));
+                    position += 1;
+                }
+                None => (),
+            }
+        }
+
+        Some(Annotation {
+            name: name.to_string(),
+            arguments: parsed,
+        })
+    }
+
+    /// Gets the value of an argument, ignoring the case of its name
+    pub fn argument(&self, key: &str) -> Option<&str> {
+        let key = normalize(key);
+        self.arguments
+            .iter()
+            .find(|(name, _)| normalize(name) == key)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Gets the value of a boolean argument
+    fn flag(&self, key: &str) -> Option<bool> {
+        match self.argument(key)?.to_lowercase().as_str() {
+            "true" => Some(true),
+            "false" => Some(false),
+            _ => None,
+        }
+    }
+
+    fn is(&self, names: &[&str]) -> bool {
+        names.contains(&normalize(&self.name).as_str())
+    }
+}
+
+/// A relationship to another entity declared by the annotations of a field
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct DeclaredRelationship {
+    /// The normalized names of the entities the field may refer to, from the most likely
+    pub(crate) targets: Vec<String>,
+    /// How many entities the field refers to, if declared
+    pub(crate) cardinality: Option<Cardinality>,
+    /// How many entities refer to the same entity, if declared
+    pub(crate) inverse: Option<Cardinality>,
+}
+
+impl Field {
+    /// Sets the raw annotations of the field, along with the identifier,
+    /// nullable and unique markers they imply
+    pub fn with_annotations(mut self, annotations: Vec<String>) -> Self {
+        self.annotations = annotations;
+        for annotation in self.parsed_annotations() {
+            if annotation.is(ID_ANNOTATIONS)
+                || annotation.flag("primary_key") == Some(true)
+                || annotation.flag("primary") == Some(true)
+            {
+                self.is_id = true;
+            }
+            if annotation.is(NOT_NULL_ANNOTATIONS) {
+                self.nullable = Some(false);
+            } else if annotation.is(&["nullable"]) {
+                self.nullable = Some(true);
+            } else if let Some(nullable) = annotation
+                .flag("nullable")
+                .or_else(|| annotation.flag("optional"))
+            {
+                self.nullable = Some(nullable);
+            }
+            if annotation.is(&["unique"]) || annotation.flag("unique") == Some(true) {
+                self.is_unique = true;
+            }
+        }
+        self
+    }
+
+    /// Parses the raw annotations of the field, skipping any that are malformed
+    pub fn parsed_annotations(&self) -> Vec<Annotation> {
+        self.annotations
+            .iter()
+            .filter_map(|annotation| Annotation::parse(annotation))
+            .collect()
+    }
+
+    /// Gets the relationship the annotations of the field declare, such as
+    /// `@ManyToOne(targetEntity = Customer.class)` or `@DBRef`
+    pub(crate) fn declared_relationship(&self) -> Option<DeclaredRelationship> {
+        use Cardinality::*;
+
+        let mut declared = DeclaredRelationship {
+            targets: vec![],
+            cardinality: None,
+            inverse: None,
+        };
+        let mut is_declared = false;
+        let mut columns = vec![];
+        for annotation in self.parsed_annotations() {
+            let (cardinality, inverse) = match normalize(&annotation.name).as_str() {
+                "onetoone" => (One, One),
+                "onetomany" => (Many, One),
+                "manytoone" | "foreignkey" => (One, Many),
+                "manytomany" => (Many, Many),
+                _ if annotation.is(REFERENCE_ANNOTATIONS) => {
+                    is_declared = true;
+                    if annotation.is(COLUMN_ANNOTATIONS) {
+                        columns.extend(annotation.argument("name").map(String::from));
+                    } else {
+                        declared.targets.extend(target(&annotation));
+                    }
+                    continue;
+                }
+                _ => continue,
+            };
+            is_declared = true;
+            declared.cardinality = Some(cardinality);
+            declared.inverse = Some(inverse);
+            declared.targets.extend(target(&annotation));
+        }
+        if !is_declared {
+            return None;
+        }
+
+        // Foreign keys are usually named after the entity they refer to, such as `customer_id`
+        for name in columns.iter().chain(std::iter::once(&self.name)) {
+            let name = normalize(name);
+            let name = name
+                .strip_suffix("ids")
+                .or_else(|| name.strip_suffix("id"))
+                .filter(|name| !name.is_empty())
+                .unwrap_or(&name);
+            declared.targets.push(name.to_string());
+            // Collections are usually named after the plural of the entity, such as `orders`
+            let singular = singular(name);
+            if singular != name {
+                declared.targets.push(singular);
+            }
+        }
+        Some(declared)
+    }
+}
+
+/// Gets the singular of a normalized name with a regular English plural, such
+/// as `orders`, `categories` or `addresses`. Irregular plurals like `people`
+/// are kept as is, and neither `-ies` plurals of words not ending in `y` like
+/// `movies` nor `-es` plurals of words ending in `s` like `statuses` are recognized
+fn singular(name: &str) -> String {
+    if let Some(stem) = name.strip_suffix("ies").filter(|stem| !stem.is_empty()) {
+        return format!("{}y", stem);
+    }
+    let sibilant = ["ss", "x", "z", "ch", "sh"];
+    if let Some(stem) = name
+        .strip_suffix("es")
+        .filter(|stem| sibilant.iter().any(|ending| stem.ends_with(ending)))
+    {
+        return stem.to_string();
+    }
+    match name.strip_suffix('s') {
+        Some(stem) if !stem.is_empty() && !stem.ends_with(['s', 'u', 'i']) => stem.to_string(),
+        _ => name.to_string(),
+    }
+}
+
+/// Gets the normalized name of the entity an annotation explicitly refers to
+fn target(annotation: &Annotation) -> Option<String> {
+    let target = annotation
+        .argument("targetEntity")
+        .or_else(|| annotation.argument("target"))
+        .or_else(|| annotation.argument("to"))
+        .or_else(|| annotation.argument("value"))?;
+    // Type functions like `() => Order` and class literals like `Order.class`
+    let target = target.rsplit("=>").next()?.trim();
+    let target = target.strip_suffix(".class").unwrap_or(target);
+    Some(normalize(&TypeExpr::parse(target).name)).filter(|target| !target.is_empty())
+}
+
+/// Splits the annotations of a field, separated by commas or by the `@` starting each
+/// of them, such as `@Id @Column(name = "id")`
+pub(crate) fn split_annotations(annotations: &str) -> Vec<String> {
+    let mut split = vec![];
+    let mut current = String::new();
+    let mut depth = 0;
+    let mut quote = None;
+    for c in annotations.chars() {
+        match (c, quote) {
+            (c, Some(open)) if c == open => quote = None,
+            (_, Some(_)) => (),
+            ('"' | '\'' | '`', None) => quote = Some(c),
+            ('(' | '[' | '{', None) => depth += 1,
+            (')' | ']' | '}', None) => depth -= 1,
+            (',' | '@', None) if depth == 0 => {
+                split.push(current.trim().to_string());
+                current.clear();
+                if c == ',' {
+                    continue;
+                }
+            }
+            _ => (),
+        }
+        current.push(c);
+    }
+    split.push(current.trim().to_string());
+    split.retain(|annotation| !annotation.is_empty());
+    split
+}
+
+/// Splits an argument like `name = "id"` into its name and unquoted value
+fn assignment(argument: &str, separator: char) -> Option<(String, String)> {
+    let (key, value) = argument.split_once(separator)?;
+    let key = key.trim();
+    // Arrow functions like `() => Order` are not assignments
+    if key.is_empty()
+        || value.starts_with('>')
+        || !key.chars().all(|c| c.is_alphanumeric() || c == '_')
+    {
+        return None;
+    }
+    Some((key.to_string(), unquote(value)))
+}
+
+fn unquote(value: &str) -> String {
+    value
+        .trim()
+        .trim_matches(|c| c == '"' || c == '\'' || c == '`')
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+
+    #[test]
+    fn parse_annotations() {
+        let column = Annotation::parse(r#"@Column(name = "customer_id", nullable = false)"#);
+        assert_eq!(
+            column.as_ref().map(|column| column.name.as_str()),
+            Some("Column")
+        );
+        assert_eq!(
+            column.as_ref().and_then(|column| column.argument("name")),
+            Some("customer_id")
+        );
+
+        let many_to_one =
+            Annotation::parse("@ManyToOne(() => User, (user) => user.photos, { nullable: true })")
+                .unwrap();
+        assert_eq!(many_to_one.argument("value"), Some("() => User"));
+        assert_eq!(many_to_one.flag("nullable"), Some(true));
+    }
+
+    #[test]
+    fn split_raw_annotations() {
+        assert_eq!(
+            split_annotations(r#"@Id @Column(name = "id, key", unique = true), @NotNull"#),
+            vec![
+                "@Id".to_string(),
+                r#"@Column(name = "id, key", unique = true)"#.to_string(),
+                "@NotNull".to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn infer_metadata() {
+        let field = Field::new("id", "Long", false).with_annotations(vec![
+            "@Id".into(),
+            "@Column(unique = true, nullable = false)".into(),
+        ]);
+        assert!(field.is_id);
+        assert!(field.is_unique);
+        assert_eq!(field.nullable, Some(false));
+        assert_eq!(field.declared_relationship(), None);
+    }
+
+    #[test]
+    fn declare_relationship() {
+        let field = Field::new("customerId", "Long", false).with_annotations(vec![
+            "@ManyToOne(fetch = FetchType.LAZY)".into(),
+            r#"@JoinColumn(name = "client_id")"#.into(),
+        ]);
+        assert_eq!(
+            field.declared_relationship(),
+            Some(DeclaredRelationship {
+                targets: vec!["client".into(), "customer".into()],
+                cardinality: Some(Cardinality::One),
+                inverse: Some(Cardinality::Many),
+            })
+        );
+
+        let field = Field::new("items", "Set<Long>", true).with_annotations(vec![
+            "@OneToMany(targetEntity = com.shop.OrderItem.class)".into(),
+        ]);
+        assert_eq!(
+            field
+                .declared_relationship()
+                .map(|declared| declared.targets),
+            Some(vec!["orderitem".into(), "items".into(), "item".into()])
+        );
+    }
+
+    #[test_case("orders" => "order" ; "regular")]
+    #[test_case("categories" => "category" ; "ies")]
+    #[test_case("addresses" => "address" ; "sses")]
+    #[test_case("boxes" => "box" ; "xes")]
+    #[test_case("branches" => "branch" ; "ches")]
+    #[test_case("courses" => "course" ; "silent e")]
+    #[test_case("address" => "address" ; "singular ss")]
+    #[test_case("status" => "status" ; "singular us")]
+    #[test_case("analysis" => "analysis" ; "singular is")]
+    #[test_case("order" => "order" ; "singular")]
+    #[test_case("people" => "people" ; "irregular")]
+    #[test_case("movies" => "movy" ; "ies not from y")]
+    #[test_case("statuses" => "statuse" ; "es after s")]
+    fn singularize(name: &str) -> String {
+        singular(name)
+    }
+}
@@ -0,0 +1,371 @@
+use std::collections::{BTreeMap, HashSet};
+
+use petgraph::{graph::NodeIndex, visit::EdgeRef};
+use serde::{Deserialize, Serialize};
+
+use crate::{DatabaseType, Entity, MicroserviceGraph};
+
+/// How many distinct microservices a hub calls or is called by
+const HUB_DEGREE: usize = 5;
+/// How many calls between two microservices calling each other make them chatty
+const CHATTY_CALLS: usize = 4;
+
+/// The kind of an architectural smell
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AntiPatternKind {
+    /// Three or more microservices that depend on each other through a cycle of
+    /// calls. Two microservices calling each other are a chatty coupling instead
+    CyclicDependency,
+    /// The same entity of the bounded context is persisted by several microservices,
+    /// which is less severe when they persist it in different categories of databases
+    SharedPersistence,
+    /// A microservice calling or called by many other microservices
+    HubService,
+    /// Two microservices calling each other
+    ChattyCoupling,
+    /// A microservice that neither calls nor is called by any other
+    OrphanService,
+}
+
+/// How much an anti-pattern is likely to hurt the system
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    Low,
+    Medium,
+    High,
+}
+
+/// An anti-pattern detected in a microservice system
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AntiPattern {
+    pub kind: AntiPatternKind,
+    pub severity: Severity,
+    /// The names of the microservices involved
+    pub services: Vec<String>,
+    /// The names of the entities involved
+    pub entities: Vec<String>,
+    /// A description of the finding
+    pub description: String,
+}
+
+impl AntiPattern {
+    fn new(
+        kind: AntiPatternKind,
+        severity: Severity,
+        services: Vec<String>,
+        description: String,
+    ) -> Self {
+        AntiPattern {
+            kind,
+            severity,
+            services,
+            entities: vec![],
+            description,
+        }
+    }
+}
+
+impl MicroserviceGraph {
+    /// Detects the anti-patterns in the microservice system, finding the entities
+    /// persisted by several microservices among the merged entities of its
+    /// bounded context
+    pub fn anti_patterns(&self, bounded_context: &[Entity]) -> Vec<AntiPattern> {
+        let graph = &self.graph;
+        let metrics = self.metrics();
+        let mut found = vec![];
+
+        // Cycles of two microservices are reported as chatty couplings below
+        let cycles = metrics
+            .system
+            .strongly_connected_components
+            .iter()
+            .filter(|component| component.len() > 2);
+        for component in cycles {
+            found.push(AntiPattern::new(
+                AntiPatternKind::CyclicDependency,
+                Severity::High,
+                component.clone(),
+                format!("{} depend on each other in a cycle", component.join(", ")),
+            ));
+        }
+
+        // Count the calls between every pair of different microservices
+        let mut calls: BTreeMap<(usize, usize), usize> = BTreeMap::new();
+        for edge in graph.edge_references() {
+            let (from, to) = (edge.source().index(), edge.target().index());
+            if from != to {
+                *calls.entry((from, to)).or_default() += 1;
+            }
+        }
+        for (&(from, to), &count) in calls.iter() {
+            let back = match calls.get(&(to, from)) {
+                Some(back) if from < to => back,
+                _ => continue,
+            };
+            let total = count + back;
+            let severity = if total >= CHATTY_CALLS {
+                Severity::High
+            } else {
+                Severity::Medium
+            };
+            let services = vec![
+                metrics.services[from].name.clone(),
+                metrics.services[to].name.clone(),
+            ];
+            found.push(AntiPattern::new(
+                AntiPatternKind::ChattyCoupling,
+                severity,
+                services.clone(),
+                format!(
+                    "{} and {} call each other {} times",
+                    services[0], services[1], total
+                ),
+            ));
+        }
+
+        for (ndx, service) in metrics.services.iter().enumerate() {
+            let degree = service.ais.max(service.ads);
+            if degree >= HUB_DEGREE {
+                let severity = if degree >= 2 * HUB_DEGREE {
+                    Severity::High
+                } else {
+                    Severity::Medium
+                };
+                found.push(AntiPattern::new(
+                    AntiPatternKind::HubService,
+                    severity,
+                    vec![service.name.clone()],
+                    format!(
+                        "{} is called by {} and calls {} microservices",
+                        service.name, service.ais, service.ads
+                    ),
+                ));
+            }
+
+            // Messaging and calls to itself still count as communicating
+            if graph
+                .neighbors_undirected(NodeIndex::new(ndx))
+                .next()
+                .is_none()
+            {
+                found.push(AntiPattern::new(
+                    AntiPatternKind::OrphanService,
+                    Severity::Low,
+                    vec![service.name.clone()],
+                    format!(
+                        "{} neither calls nor is called by any microservice",
+                        service.name
+                    ),
+                ));
+            }
+        }
+
+        // The sources of a merged entity are the same entity in every microservice
+        for entity in bounded_context {
+            let mut services: Vec<String> = vec![];
+            let mut databases: Vec<DatabaseType> = vec![];
+            for source in entity.sources.iter() {
+                if let Some(ms) = &source.microservice {
+                    if !services.contains(ms) {
+                        services.push(ms.clone());
+                    }
+                }
+                if !databases.contains(&source.ty) {
+                    databases.push(source.ty.clone());
+                }
+            }
+            if services.len() < 2 {
+                continue;
+            }
+
+            // Copies in different kinds of databases are more likely to be
+            // deliberate, such as a search index of a relational table
+            let categories: HashSet<_> = databases.iter().map(DatabaseType::category).collect();
+            let severity = if categories.len() > 1 {
+                Severity::Low
+            } else if services.len() > 2 {
+                Severity::High
+            } else {
+                Severity::Medium
+            };
+            let databases: Vec<_> = databases.iter().map(DatabaseType::to_string).collect();
+            let description = format!(
+                "{} is persisted by {} in {}",
+                entity.name,
+                services.join(", "),
+                databases.join(", ")
+            );
+            found.push(AntiPattern {
+                entities: vec![entity.name.clone()],
+                ..AntiPattern::new(
+                    AntiPatternKind::SharedPersistence,
+                    severity,
+                    services,
+                    description,
+                )
+            });
+        }
+
+        found
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+    use crate::{testing::microservice_graph, CallType, EntitySource, MicroserviceCall};
+
+    fn merged(name: &str, sources: &[(&str, DatabaseType)]) -> Entity {
+        let sources = sources
+            .iter()
+            .map(|(ms, ty)| EntitySource {
+                microservice: Some(ms.to_string()),
+                entity: name.into(),
+                ty: ty.clone(),
+            })
+            .collect();
+        Entity {
+            sources,
+            ..Entity::new(name, vec![], DatabaseType::MySQL)
+        }
+    }
+
+    /// Detects the anti-patterns, sorted by their kind and then their sorted services
+    fn detect(
+        services: &[&str],
+        calls: &[(usize, usize)],
+        bounded_context: &[Entity],
+    ) -> Vec<(AntiPatternKind, Severity, Vec<String>)> {
+        let calls = calls
+            .iter()
+            .map(|&(from, to)| (from, to, MicroserviceCall::new(CallType::Rpc)));
+        let graph = microservice_graph(services, calls);
+
+        let mut found: Vec<_> = graph
+            .anti_patterns(bounded_context)
+            .into_iter()
+            .map(|mut anti_pattern| {
+                anti_pattern.services.sort();
+                (
+                    anti_pattern.kind,
+                    anti_pattern.severity,
+                    anti_pattern.services,
+                )
+            })
+            .collect();
+        found.sort_by_key(|(kind, _, services)| (*kind as u8, services.clone()));
+        found
+    }
+
+    fn names(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn detect_cycles_and_chatty_couplings() {
+        use AntiPatternKind::*;
+
+        // a and b call each other, which is not also reported as a cycle
+        let found = detect(&["a", "b"], &[(0, 1), (1, 0)], &[]);
+        assert_eq!(
+            found,
+            vec![(ChattyCoupling, Severity::Medium, names(&["a", "b"]))]
+        );
+
+        let found = detect(&["a", "b", "c"], &[(0, 1), (1, 2), (2, 0), (0, 1)], &[]);
+        assert_eq!(
+            found,
+            vec![(CyclicDependency, Severity::High, names(&["a", "b", "c"]))]
+        );
+
+        let calls = [(0, 1), (1, 0), (0, 1), (1, 0)];
+        let found = detect(&["a", "b"], &calls, &[]);
+        assert_eq!(
+            found,
+            vec![(ChattyCoupling, Severity::High, names(&["a", "b"]))]
+        );
+    }
+
+    #[test_case(2 => Some(Severity::Medium) ; "two calls")]
+    #[test_case(3 => Some(Severity::Medium) ; "below threshold")]
+    #[test_case(4 => Some(Severity::High) ; "at threshold")]
+    #[test_case(5 => Some(Severity::High) ; "above threshold")]
+    fn grade_chatty_couplings(calls: usize) -> Option<Severity> {
+        // a calls b once, and b calls a the rest of the times
+        let calls: Vec<_> = std::iter::once((0, 1))
+            .chain(std::iter::repeat((1, 0)).take(calls - 1))
+            .collect();
+        detect(&["a", "b"], &calls, &[])
+            .into_iter()
+            .find(|(kind, _, _)| *kind == AntiPatternKind::ChattyCoupling)
+            .map(|(_, severity, _)| severity)
+    }
+
+    #[test_case(4 => None ; "below threshold")]
+    #[test_case(5 => Some(Severity::Medium) ; "at threshold")]
+    #[test_case(9 => Some(Severity::Medium) ; "below twice the threshold")]
+    #[test_case(10 => Some(Severity::High) ; "at twice the threshold")]
+    fn grade_hubs(callees: usize) -> Option<Severity> {
+        let names: Vec<_> = (0..=callees).map(|ndx| format!("service{}", ndx)).collect();
+        let services: Vec<_> = names.iter().map(String::as_str).collect();
+        let calls: Vec<_> = (1..=callees).map(|callee| (0, callee)).collect();
+        detect(&services, &calls, &[])
+            .into_iter()
+            .find(|(kind, _, _)| *kind == AntiPatternKind::HubService)
+            .map(|(_, severity, _)| severity)
+    }
+
+    #[test]
+    fn detect_hubs_and_orphans() {
+        use AntiPatternKind::*;
+
+        let services = ["hub", "a", "b", "c", "d", "e", "orphan"];
+        let calls: Vec<_> = (1..=5).map(|callee| (0, callee)).collect();
+        let found = detect(&services, &calls, &[]);
+        assert_eq!(
+            found,
+            vec![
+                (HubService, Severity::Medium, names(&["hub"])),
+                (OrphanService, Severity::Low, names(&["orphan"])),
+            ]
+        );
+    }
+
+    #[test]
+    fn detect_shared_persistence_of_merged_entities() {
+        use AntiPatternKind::*;
+        use DatabaseType::*;
+
+        let services = ["orders", "payments", "search"];
+        let calls = [(0, 1), (1, 2)];
+        let bounded_context = [
+            // Merged from differently named entities of two microservices
+            merged("Order", &[("orders", MySQL), ("payments", PostgreSQL)]),
+            merged("Customer", &[("orders", MySQL), ("search", Elasticsearch)]),
+            merged(
+                "Invoice",
+                &[("payments", PostgreSQL), ("payments", PostgreSQL)],
+            ),
+        ];
+        let found = detect(&services, &calls, &bounded_context);
+        assert_eq!(
+            found,
+            vec![
+                (
+                    SharedPersistence,
+                    Severity::Medium,
+                    names(&["orders", "payments"])
+                ),
+                (
+                    SharedPersistence,
+                    Severity::Low,
+                    names(&["orders", "search"])
+                ),
+            ]
+        );
+    }
+}
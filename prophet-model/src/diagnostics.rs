@@ -0,0 +1,147 @@
+use std::collections::BTreeMap;
+
+use runestick::Value;
+use serde::{Deserialize, Serialize};
+use source_code_parser::ressa;
+
+/// The message of the error for a call or endpoint with an unknown HTTP method
+pub(crate) const BAD_HTTP_METHOD: &str = "Bad HTTP method";
+
+/// The kind of ReSSA object that was skipped
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ObjectKind {
+    Service,
+    Call,
+    Endpoint,
+    Entity,
+    Field,
+}
+
+/// Why a ReSSA object was skipped
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DiagnosticKind {
+    /// A required key of the object is missing
+    MissingKey,
+    /// The HTTP method of a call or endpoint is not a valid method
+    BadHttpMethod,
+    /// A call names a microservice that was not detected
+    UnknownCallee,
+    /// The object could not be read for any other reason
+    Malformed,
+}
+
+/// A ReSSA object that was skipped while building the model
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Diagnostic {
+    pub kind: DiagnosticKind,
+    /// The kind of the skipped object
+    pub object: ObjectKind,
+    /// The name of the object containing the skipped object, such as the
+    /// microservice of a call
+    pub parent: Option<String>,
+    /// The name of the skipped object, if it has one
+    pub name: Option<String>,
+    /// What was wrong with the object
+    pub message: String,
+}
+
+impl Diagnostic {
+    /// Creates a diagnostic for a ReSSA object that could not be read, reporting
+    /// the first of its required keys that is missing
+    pub(crate) fn from_error(
+        object: ObjectKind,
+        parent: Option<&str>,
+        value: &BTreeMap<String, Value>,
+        required: &[&str],
+        err: ressa::Error,
+    ) -> Diagnostic {
+        let name = ressa::extract(value, "name", Value::into_string).ok();
+        let (kind, message) = match required.iter().find(|key| !value.contains_key(**key)) {
+            Some(key) => (DiagnosticKind::MissingKey, format!("Missing key '{}'", key)),
+            None => error_kind(err),
+        };
+        Diagnostic {
+            kind,
+            object,
+            parent: parent.map(String::from),
+            name,
+            message,
+        }
+    }
+
+    /// Creates a diagnostic for a ReSSA object written as a string, such as an
+    /// endpoint like `GET /api/v1/orders`, that could not be read
+    pub(crate) fn from_string_error(
+        object: ObjectKind,
+        parent: Option<&str>,
+        value: &str,
+        err: ressa::Error,
+    ) -> Diagnostic {
+        let (kind, message) = error_kind(err);
+        Diagnostic {
+            kind,
+            object,
+            parent: parent.map(String::from),
+            name: Some(value.to_string()),
+            message,
+        }
+    }
+}
+
+fn error_kind(err: ressa::Error) -> (DiagnosticKind, String) {
+    match err {
+        ressa::Error::InvalidType(message) if message == BAD_HTTP_METHOD => {
+            (DiagnosticKind::BadHttpMethod, message)
+        }
+        err => (DiagnosticKind::Malformed, format!("{:?}", err)),
+    }
+}
+
+/// Collects the ReSSA objects skipped while building the model
+#[derive(Debug, Clone, Default)]
+pub struct Diagnostics(Vec<Diagnostic>);
+
+impl Diagnostics {
+    pub fn push(&mut self, diagnostic: Diagnostic) {
+        self.0.push(diagnostic);
+    }
+
+    /// Reads every object with `read`, keeping the objects that could be read
+    /// and recording a diagnostic for the others
+    pub(crate) fn collect<T>(
+        &mut self,
+        objects: impl IntoIterator<Item = BTreeMap<String, Value>>,
+        object: ObjectKind,
+        parent: Option<&str>,
+        required: &[&str],
+        mut read: impl FnMut(&BTreeMap<String, Value>, &mut Diagnostics) -> Result<T, ressa::Error>,
+    ) -> Vec<T> {
+        objects
+            .into_iter()
+            .filter_map(|value| match read(&value, self) {
+                Ok(read) => Some(read),
+                Err(err) => {
+                    self.push(Diagnostic::from_error(
+                        object, parent, &value, required, err,
+                    ));
+                    None
+                }
+            })
+            .collect()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Diagnostic> {
+        self.0.iter()
+    }
+
+    /// Converts the diagnostics into its inner representation
+    pub fn into_inner(self) -> Vec<Diagnostic> {
+        self.0
+    }
+}
@@ -0,0 +1,281 @@
+use std::{collections::BTreeMap, str::FromStr};
+
+use runestick::Value;
+use serde::{Deserialize, Serialize};
+use source_code_parser::ressa;
+
+use crate::{serialization::http_method, BAD_HTTP_METHOD};
+
+/// An HTTP endpoint exposed by a microservice
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Endpoint {
+    /// The HTTP method the endpoint accepts
+    #[serde(with = "http_method")]
+    pub method: http::Method,
+    /// The normalized path template of the endpoint, where every path
+    /// variable is replaced with `{}`
+    pub path: String,
+    /// The function handling requests to the endpoint
+    pub handler: Option<String>,
+    /// The names of the path variables, followed by the types of any other
+    /// parameters of the handler
+    pub parameters: Vec<String>,
+    /// The type returned by the handler
+    pub return_type: Option<String>,
+}
+
+impl Endpoint {
+    pub fn new(method: http::Method, path: &str) -> Self {
+        Endpoint {
+            method,
+            path: normalize_path(path),
+            handler: None,
+            parameters: path_variables(path),
+            return_type: None,
+        }
+    }
+
+    /// Whether a request with the method to the normalized path is handled by the
+    /// endpoint. A `{}` segment on either side matches any single segment
+    pub fn matches(&self, method: &http::Method, path: &str) -> bool {
+        self.specificity(method, path).is_some()
+    }
+
+    /// How specifically the endpoint handles a request with the method to the
+    /// normalized path, as the number of literal segments both have in common,
+    /// or `None` if the endpoint does not handle the request at all
+    pub fn specificity(&self, method: &http::Method, path: &str) -> Option<usize> {
+        let segments: Vec<_> = self.path.split('/').filter(|s| !s.is_empty()).collect();
+        let other: Vec<_> = path.split('/').filter(|s| !s.is_empty()).collect();
+        if self.method != *method || segments.len() != other.len() {
+            return None;
+        }
+
+        let mut literal = 0;
+        for (a, b) in segments.iter().zip(other.iter()) {
+            if *a == "{}" || *b == "{}" {
+                continue;
+            }
+            if a != b {
+                return None;
+            }
+            literal += 1;
+        }
+        Some(literal)
+    }
+}
+
+impl FromStr for Endpoint {
+    type Err = ressa::Error;
+
+    /// Parses an endpoint in the `METHOD /path/{var} ReturnType` format emitted
+    /// by the endpoint ReSSAs, where the return type is optional
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.split_whitespace();
+        let (method, path) = match (parts.next(), parts.next()) {
+            (Some(method), Some(path)) => (method, path),
+            _ => return Err(ressa::Error::InvalidType(format!("Bad endpoint: {}", s))),
+        };
+        let method = http::Method::from_str(&method.to_uppercase())
+            .map_err(|_| ressa::Error::InvalidType(BAD_HTTP_METHOD.into()))?;
+
+        let return_type = parts.collect::<Vec<_>>().join(" ");
+        Ok(Endpoint {
+            return_type: Some(return_type).filter(|ty| !ty.is_empty()),
+            ..Endpoint::new(method, path)
+        })
+    }
+}
+
+impl TryFrom<&BTreeMap<String, Value>> for Endpoint {
+    type Error = ressa::Error;
+
+    /// Attempts to create an endpoint from a ReSSA object with a `method` and
+    /// `path`, and the optional `handler`, `parameters` (comma separated) and
+    /// `return_type`
+    fn try_from(endpoint: &BTreeMap<String, Value>) -> Result<Self, Self::Error> {
+        let method = ressa::extract(endpoint, "method", Value::into_string)?;
+        let method = http::Method::from_str(&method.to_uppercase())
+            .map_err(|_| ressa::Error::InvalidType(BAD_HTTP_METHOD.into()))?;
+        let path = ressa::extract(endpoint, "path", Value::into_string)?;
+
+        let optional = |key: &str| {
+            ressa::extract(endpoint, key, Value::into_string)
+                .ok()
+                .filter(|value| !value.is_empty())
+        };
+        let mut parameters = path_variables(&path);
+        if let Some(others) = optional("parameters") {
+            parameters.extend(
+                others
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|parameter| !parameter.is_empty())
+                    .map(String::from),
+            );
+        }
+
+        Ok(Endpoint {
+            method,
+            path: normalize_path(&path),
+            handler: optional("handler"),
+            parameters,
+            return_type: optional("return_type"),
+        })
+    }
+}
+
+/// Normalizes a path template so equivalent paths compare equal. The path
+/// always starts with a single `/`, has no empty or trailing segments and has
+/// every `{variable}` replaced with `{}`
+pub fn normalize_path(path: &str) -> String {
+    let segments: Vec<_> = path
+        .split('/')
+        .filter(|segment| !segment.is_empty())
+        .map(|segment| {
+            if segment.starts_with('{') && segment.ends_with('}') {
+                "{}"
+            } else {
+                segment
+            }
+        })
+        .collect();
+    format!("/{}", segments.join("/"))
+}
+
+/// Normalizes the URL of a call site into a path template to match against
+/// endpoints. The scheme, host, query and fragment are dropped, and anything
+/// that is not a string literal in a concatenated URL such as
+/// `"http://orders/api/" + id + "/items"` becomes a `{}` variable
+pub fn normalize_url(url: &str) -> String {
+    let url = substitute_variables(url);
+    let path = match url.find("://") {
+        Some(ndx) => {
+            let rest = &url[ndx + 3..];
+            rest.find('/').map(|ndx| &rest[ndx..]).unwrap_or_default()
+        }
+        None => url.as_str(),
+    };
+    let path = path
+        .split(|c: char| c == '?' || c == '#')
+        .next()
+        .unwrap_or_default();
+
+    // Partially variable segments like `order-{}` can be anything
+    let segments: Vec<_> = path
+        .split('/')
+        .map(|segment| {
+            if segment.contains("{}") {
+                "{}"
+            } else {
+                segment
+            }
+        })
+        .collect();
+    normalize_path(&segments.join("/"))
+}
+
+/// Gets the host of a URL, if it has one
+pub fn url_host(url: &str) -> Option<&str> {
+    let rest = &url[url.find("://")? + 3..];
+    let authority = rest.split('/').next()?;
+    let host = authority.rsplit('@').next()?.split(':').next()?;
+    Some(host).filter(|host| !host.is_empty())
+}
+
+/// Replaces the non-literal parts of a concatenated URL, and any `${var}` or
+/// `{var}` placeholders, with `{}`
+fn substitute_variables(url: &str) -> String {
+    let url = if url.contains('"') {
+        url.split('+')
+            .map(str::trim)
+            .map(
+                |part| match part.strip_prefix('"').and_then(|p| p.strip_suffix('"')) {
+                    Some(literal) => literal,
+                    None => "{}",
+                },
+            )
+            .collect::<String>()
+    } else {
+        url.to_string()
+    };
+
+    let mut substituted = String::with_capacity(url.len());
+    let mut in_variable = false;
+    for c in url.chars() {
+        match c {
+            '{' if !in_variable => {
+                in_variable = true;
+                if substituted.ends_with('$') {
+                    substituted.pop();
+                }
+                substituted.push_str("{}");
+            }
+            '}' if in_variable => in_variable = false,
+            _ if in_variable => (),
+            c => substituted.push(c),
+        }
+    }
+    substituted
+}
+
+/// Gets the names of the `{variable}` segments of a path template
+fn path_variables(path: &str) -> Vec<String> {
+    path.split('/')
+        .filter_map(|segment| segment.strip_prefix('{')?.strip_suffix('}'))
+        .filter(|name| !name.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_endpoint() {
+        let endpoint: Endpoint = "GET /api/v1/orders/{orderId}/ Response<Order>"
+            .parse()
+            .unwrap();
+        assert_eq!(http::Method::GET, endpoint.method);
+        assert_eq!("/api/v1/orders/{}", endpoint.path);
+        assert_eq!(vec!["orderId"], endpoint.parameters);
+        assert_eq!(Some("Response<Order>".into()), endpoint.return_type);
+        assert!("/api/v1/orders".parse::<Endpoint>().is_err());
+    }
+
+    #[test]
+    fn match_call_urls() {
+        let endpoint: Endpoint = "GET /api/v1/orders/{orderId}/items".parse().unwrap();
+        let urls = [
+            "http://ts-order-service:12031/api/v1/orders/{id}/items?all=true",
+            r#""http://ts-order-service:12031/api/v1/orders/" + order.getId() + "/items""#,
+            "/api/v1/orders/${id}/items",
+        ];
+        for url in urls {
+            assert!(
+                endpoint.matches(&http::Method::GET, &normalize_url(url)),
+                "{}",
+                url
+            );
+        }
+        assert!(!endpoint.matches(&http::Method::POST, &normalize_url(urls[0])));
+        assert!(!endpoint.matches(&http::Method::GET, "/api/v1/orders/{}"));
+        assert_eq!(Some("ts-order-service"), url_host(urls[0]));
+    }
+
+    #[test]
+    fn count_literal_segments() {
+        let variable: Endpoint = "GET /api/v1/orders/{orderId}".parse().unwrap();
+        let literal: Endpoint = "GET /api/v1/orders/latest".parse().unwrap();
+        let get = &http::Method::GET;
+        assert_eq!(Some(3), variable.specificity(get, "/api/v1/orders/latest"));
+        assert_eq!(Some(4), literal.specificity(get, "/api/v1/orders/latest"));
+        assert_eq!(Some(3), literal.specificity(get, "/api/v1/orders/{}"));
+        assert_eq!(None, literal.specificity(get, "/api/v1/orders"));
+        assert_eq!(
+            None,
+            literal.specificity(&http::Method::PUT, "/api/v1/orders/latest")
+        );
+    }
+}
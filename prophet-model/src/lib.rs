@@ -6,75 +6,420 @@ use petgraph::{
     visit::EdgeRef,
 };
 use runestick::Value;
+use serde::{Deserialize, Serialize};
 use source_code_parser::{ressa, ressa::RessaResult, Language};
 use strum::Display;
 
+pub(crate) mod endpoint;
+pub use endpoint::*;
+
+pub(crate) mod diagnostics;
+pub use diagnostics::*;
+
+pub(crate) mod metrics;
+pub use metrics::*;
+
+pub(crate) mod anti_patterns;
+pub use anti_patterns::*;
+
+pub(crate) mod type_expr;
+pub use type_expr::*;
+
+pub(crate) mod relationship;
+pub use relationship::*;
+
+pub(crate) mod annotation;
+pub use annotation::*;
+
+pub(crate) mod serialization;
+use serialization::*;
+
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+
 /// A microservice detected from a ReSSA
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Microservice {
     pub name: String,
     pub language: Language,
     pub ref_entities: Vec<Entity>,
+    /// The HTTP endpoints the microservice exposes
+    pub endpoints: Vec<Endpoint>,
 }
 
-impl TryFrom<&BTreeMap<String, Value>> for Microservice {
-    type Error = ressa::Error;
-
-    /// Attempts to create a microservice from a ReSSA's object
-    fn try_from(service: &BTreeMap<String, Value>) -> Result<Self, Self::Error> {
+impl Microservice {
+    /// Attempts to create a microservice from a ReSSA's object, recording the
+    /// entities and endpoints that had to be skipped
+    pub fn from_ressa(
+        service: &BTreeMap<String, Value>,
+        diagnostics: &mut Diagnostics,
+    ) -> Result<Self, ressa::Error> {
         let name = ressa::extract(service, "name", Value::into_string)?;
         let language =
             ressa::extract(service, "language", Value::into_string).map(Language::from)?;
-        let ref_entities = ressa::extract_vec(service, "entities", Value::into_object)?
+        let entities = ressa::extract_vec(service, "entities", Value::into_object)?
             .into_iter()
-            .map(ressa::extract_object)
-            .flat_map(|entity| Entity::try_from(&entity))
-            .collect::<Vec<_>>();
+            .map(ressa::extract_object);
+        let ref_entities = diagnostics
+            .collect(
+                entities,
+                ObjectKind::Entity,
+                Some(&name),
+                &["name", "type", "fields"],
+                Entity::from_ressa,
+            )
+            .into_iter()
+            .map(|entity| Entity {
+                sources: vec![EntitySource {
+                    microservice: Some(name.clone()),
+                    entity: entity.name.clone(),
+                    ty: entity.ty.clone(),
+                }],
+                ..entity
+            })
+            .collect();
+
+        // Not every ReSSA detects endpoints, so they are optional. They are either
+        // objects or strings like `GET /api/v1/orders Order`, and an unreadable list
+        // of them does not stop the rest of the microservice from being read
+        let endpoints = if !service.contains_key("endpoints") {
+            vec![]
+        } else if let Ok(endpoints) = ressa::extract_vec(service, "endpoints", Value::into_object) {
+            diagnostics.collect(
+                endpoints.into_iter().map(ressa::extract_object),
+                ObjectKind::Endpoint,
+                Some(&name),
+                &["method", "path"],
+                |endpoint, _| Endpoint::try_from(endpoint),
+            )
+        } else {
+            match ressa::extract_vec(service, "endpoints", Value::into_string) {
+                Ok(endpoints) => endpoints
+                    .iter()
+                    .filter_map(|endpoint| match Endpoint::from_str(endpoint) {
+                        Ok(endpoint) => Some(endpoint),
+                        Err(err) => {
+                            diagnostics.push(Diagnostic::from_string_error(
+                                ObjectKind::Endpoint,
+                                Some(&name),
+                                endpoint,
+                                err,
+                            ));
+                            None
+                        }
+                    })
+                    .collect(),
+                Err(err) => {
+                    diagnostics.push(Diagnostic {
+                        kind: DiagnosticKind::Malformed,
+                        object: ObjectKind::Endpoint,
+                        parent: Some(name.clone()),
+                        name: None,
+                        message: format!(
+                            "The endpoints are neither objects nor strings: {:?}",
+                            err
+                        ),
+                    });
+                    vec![]
+                }
+            }
+        };
+
         Ok(Microservice {
             name,
             language,
             ref_entities,
+            endpoints,
         })
     }
 }
 
-/// Represents a call between microservices
-#[derive(Debug, Clone, Display)]
-pub enum MicroserviceCall {
-    Http(http::Method),
+impl TryFrom<&BTreeMap<String, Value>> for Microservice {
+    type Error = ressa::Error;
+
+    /// Attempts to create a microservice from a ReSSA's object
+    fn try_from(service: &BTreeMap<String, Value>) -> Result<Self, Self::Error> {
+        Microservice::from_ressa(service, &mut Diagnostics::default())
+    }
+}
+
+/// The kind of call made between microservices, serialized in JSON as
+/// `{"http": "GET"}`, `"rpc"` or
+/// `{"messaging": {"broker": "Kafka", "topic": "orders", "role": "producer"}}`
+#[derive(Debug, Clone, Display, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CallType {
+    Http(#[serde(with = "http_method")] http::Method),
     #[strum(serialize = "RPC")]
     Rpc,
+    /// An asynchronous message sent through a broker
+    Messaging {
+        broker: Broker,
+        /// The topic or queue the message is sent to
+        topic: String,
+        role: MessagingRole,
+    },
+}
+
+/// A message broker that microservices communicate through, serialized as its name
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(from = "String", into = "String")]
+pub enum Broker {
+    Kafka,
+    RabbitMq,
+    Jms,
+    Unknown(String),
+}
+
+impl From<String> for Broker {
+    fn from(value: String) -> Self {
+        match &*value.to_lowercase() {
+            "kafka" => Broker::Kafka,
+            "rabbitmq" | "amqp" => Broker::RabbitMq,
+            "jms" => Broker::Jms,
+            _ => Broker::Unknown(value),
+        }
+    }
+}
+
+impl From<Broker> for String {
+    fn from(broker: Broker) -> Self {
+        broker.to_string()
+    }
+}
+
+impl std::fmt::Display for Broker {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Broker::Kafka => write!(f, "Kafka"),
+            Broker::RabbitMq => write!(f, "RabbitMQ"),
+            Broker::Jms => write!(f, "JMS"),
+            Broker::Unknown(broker) => write!(f, "{}", broker),
+        }
+    }
+}
+
+/// Whether a microservice sends or receives the messages of a topic
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Display, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MessagingRole {
+    Producer,
+    Consumer,
+}
+
+/// Where a call is made in the source code of the calling microservice
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SourceLocation {
+    pub file: String,
+    pub line: Option<u32>,
+}
+
+impl std::fmt::Display for SourceLocation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "{}:{}", self.file, line),
+            None => write!(f, "{}", self.file),
+        }
+    }
+}
+
+/// Represents a call between microservices
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MicroserviceCall {
+    /// The kind of call
+    pub ty: CallType,
+    /// The URL or path template of the called endpoint
+    pub endpoint: Option<String>,
+    /// The method making the call in the calling microservice
+    pub calling_method: Option<String>,
+    /// The function handling the call in the called microservice
+    pub endpoint_function: Option<String>,
+    /// The types of the arguments passed to the endpoint
+    pub arguments: Vec<String>,
+    /// The type returned by the endpoint
+    pub return_type: Option<String>,
+    /// Where the call is made
+    pub location: Option<SourceLocation>,
+    /// The endpoint of the called microservice the call was resolved to
+    pub target: Option<Endpoint>,
+}
+
+impl MicroserviceCall {
+    pub fn new(ty: CallType) -> Self {
+        MicroserviceCall {
+            ty,
+            endpoint: None,
+            calling_method: None,
+            endpoint_function: None,
+            arguments: vec![],
+            return_type: None,
+            location: None,
+            target: None,
+        }
+    }
 }
 
 impl TryFrom<&BTreeMap<String, Value>> for MicroserviceCall {
     type Error = ressa::Error;
 
     /// Attempts to convert a ReSSA object to a microservice call
+    ///
+    /// Messaging calls of `type` `MESSAGING` have a `broker`, a `topic` and a `role`
+    /// of either `producer` or `consumer` instead of an HTTP `method`.
+    ///
+    /// Besides the call's `type` and HTTP `method`, the optional `endpoint`,
+    /// `calling_method`, `endpoint_function`, `arguments` (comma separated types),
+    /// `return_type`, `file` and `line` are read from the object
     fn try_from(call: &BTreeMap<String, Value>) -> Result<Self, Self::Error> {
         let ty = ressa::extract(call, "type", Value::into_string)?;
         let method = ressa::extract(call, "method", Value::into_string);
-        let call = match method {
-            Ok(method) if ty == "HTTP" => MicroserviceCall::Http(
+        let ty = match method {
+            Ok(method) if ty == "HTTP" => CallType::Http(
                 http::Method::from_str(&method)
-                    .map_err(|_| ressa::Error::InvalidType("Bad HTTP method".into()))?,
+                    .map_err(|_| ressa::Error::InvalidType(BAD_HTTP_METHOD.into()))?,
             ),
-            Err(_) if ty == "RPC" => MicroserviceCall::Rpc,
+            Err(_) if ty == "RPC" => CallType::Rpc,
+            Err(_) if ty == "MESSAGING" => {
+                let role = match &*ressa::extract(call, "role", Value::into_string)?.to_lowercase()
+                {
+                    "producer" => MessagingRole::Producer,
+                    "consumer" => MessagingRole::Consumer,
+                    _ => return Err(ressa::Error::InvalidType("Bad messaging role".into())),
+                };
+                CallType::Messaging {
+                    broker: ressa::extract(call, "broker", Value::into_string)?.into(),
+                    topic: ressa::extract(call, "topic", Value::into_string)?,
+                    role,
+                }
+            }
             _ => {
                 return Err(ressa::Error::InvalidType(
                     "Bad microservice call type".into(),
                 ))
             }
         };
-        Ok(call)
+
+        // The metadata is optional, as not every ReSSA can detect all of it
+        let optional = |key: &str| {
+            ressa::extract(call, key, Value::into_string)
+                .ok()
+                .filter(|value| !value.is_empty())
+        };
+        let arguments = optional("arguments")
+            .map(|arguments| {
+                arguments
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|argument| !argument.is_empty())
+                    .map(String::from)
+                    .collect()
+            })
+            .unwrap_or_default();
+        let location = optional("file").map(|file| SourceLocation {
+            file,
+            line: ressa::extract_primitive(call, "line", Value::into_integer)
+                .ok()
+                .and_then(|line| u32::try_from(line).ok()),
+        });
+
+        Ok(MicroserviceCall {
+            ty,
+            endpoint: optional("endpoint"),
+            calling_method: optional("calling_method"),
+            endpoint_function: optional("endpoint_function"),
+            arguments,
+            return_type: optional("return_type"),
+            location,
+            target: None,
+        })
     }
 }
 
+/// A call that could not be resolved to the microservice or endpoint it calls
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UnresolvedCall {
+    /// The name of the calling microservice
+    pub caller: String,
+    /// The name of the called microservice, if it is known
+    pub callee: Option<String>,
+    /// The URL or path template of the call, if it is known
+    pub endpoint: Option<String>,
+    /// Why the call could not be resolved
+    pub reason: String,
+}
+
 /// A graph of calls between microservices
-#[derive(Debug, Clone)]
-pub struct MicroserviceGraph(DiGraph<Microservice, MicroserviceCall>);
+///
+/// The serialized representation in JSON lists the microservices as nodes and
+/// the calls as edges between the indices of the nodes
+/// ```json
+/// {
+///   "nodes": [
+///     { "name": "orders", "language": "Java", "ref_entities": [], "endpoints": [] },
+///     {
+///       "name": "users",
+///       "language": "Java",
+///       "ref_entities": [],
+///       "endpoints": [
+///         {
+///           "method": "GET",
+///           "path": "/users/{}",
+///           "handler": "getUser",
+///           "parameters": ["id"],
+///           "return_type": "User"
+///         }
+///       ]
+///     }
+///   ],
+///   "edges": [
+///     {
+///       "from": 0,
+///       "to": 1,
+///       "weight": {
+///         "ty": { "http": "GET" },
+///         "endpoint": "/users/{}",
+///         "calling_method": "UserClient.getUser",
+///         "endpoint_function": null,
+///         "arguments": ["Long"],
+///         "return_type": "User",
+///         "location": { "file": "src/UserClient.java", "line": 12 },
+///         "target": null
+///       }
+///     }
+///   ],
+///   "unresolved_calls": [
+///     { "caller": "orders", "callee": "billing", "endpoint": "/bills", "reason": "Unknown microservice" }
+///   ],
+///   "diagnostics": [
+///     {
+///       "kind": "missing_key",
+///       "object": "field",
+///       "parent": "Order",
+///       "name": null,
+///       "message": "Missing key 'type'"
+///     }
+///   ]
+/// }
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(into = "MicroserviceGraphRepr", try_from = "MicroserviceGraphRepr")]
+pub struct MicroserviceGraph {
+    graph: DiGraph<Microservice, MicroserviceCall>,
+    unresolved_calls: Vec<UnresolvedCall>,
+    diagnostics: Vec<Diagnostic>,
+}
 
 impl MicroserviceGraph {
     /// Attempts to create a microservice graph from a ReSSA result
+    ///
+    /// HTTP calls are resolved to the endpoint of the called microservice whose
+    /// method and path template match the call's URL. Calls to a microservice
+    /// that cannot be found, or to an endpoint it does not expose, are kept as
+    /// [`UnresolvedCall`]s instead. Messaging producers are connected to the
+    /// consumers of the same topic on the same broker, and producers or consumers
+    /// without a counterpart are kept as unresolved calls too. Any services,
+    /// calls, endpoints, entities and fields that cannot be read are skipped and
+    /// recorded as [`Diagnostic`]s
     pub fn try_new(result: &RessaResult) -> Option<MicroserviceGraph> {
         let ctx = result.get("ctx")?;
         // Get the services shared vec from the context
@@ -84,63 +429,339 @@ impl MicroserviceGraph {
             .map(ressa::extract_object)
             .collect::<Vec<_>>();
 
-        // Create the graph with the service nodes
+        // Create the graph with the service nodes, skipping those that cannot be read
+        let mut diagnostics = Diagnostics::default();
         let mut graph: DiGraph<Microservice, MicroserviceCall> = DiGraph::new();
-        let indices = add_nodes(&mut graph, &services);
-
-        // Get the calls each of the services makes
-        let services = services.iter().flat_map(|service| {
-            let name = Microservice::try_from(service)?.name;
-            let calls = ressa::extract_vec(service, "calls", Value::into_object)?
-                .into_iter()
-                .map(ressa::result::extract_object)
-                .collect::<Vec<_>>();
-            Ok::<_, ressa::Error>((name, calls))
-        });
+        let mut nodes = vec![];
+        for service in services.iter() {
+            match Microservice::from_ressa(service, &mut diagnostics) {
+                Ok(ms) => nodes.push((graph.add_node(ms), service)),
+                Err(err) => diagnostics.push(Diagnostic::from_error(
+                    ObjectKind::Service,
+                    None,
+                    service,
+                    &["name", "language", "entities"],
+                    err,
+                )),
+            }
+        }
+        let indices: Vec<_> = nodes.iter().map(|(ndx, _)| *ndx).collect();
 
-        // Add directed edges between services in the graph
-        for (service_name, calls) in services {
-            let service_ndx = indices
-                .iter()
-                .find(|ndx| graph[**ndx].name == service_name)?;
+        // Add directed edges between services in the graph, remembering the
+        // messaging calls to connect through their topics afterwards
+        let mut messaging = vec![];
+        let mut unresolved_calls = vec![];
+        for (service_ndx, service) in nodes.iter() {
+            let service_name = graph[*service_ndx].name.clone();
 
-            for call in calls.iter() {
-                let called_name = ressa::extract(call, "name", Value::into_string).ok()?;
-                let called_service_ndx = indices
-                    .iter()
-                    .find(|ndx| graph[**ndx].name == called_name)?;
-                let call = call.try_into().ok()?;
+            // Services that make no calls may omit them
+            if !service.contains_key("calls") {
+                continue;
+            }
+            let calls = match ressa::extract_vec(service, "calls", Value::into_object) {
+                Ok(calls) => calls.into_iter().map(ressa::result::extract_object),
+                Err(err) => {
+                    diagnostics.push(Diagnostic::from_error(
+                        ObjectKind::Service,
+                        None,
+                        service,
+                        &[],
+                        err,
+                    ));
+                    continue;
+                }
+            };
+
+            for call in calls {
+                let called_name = ressa::extract(&call, "name", Value::into_string).ok();
+                let mut parsed: MicroserviceCall = match (&call).try_into() {
+                    Ok(parsed) => parsed,
+                    Err(err) => {
+                        diagnostics.push(Diagnostic::from_error(
+                            ObjectKind::Call,
+                            Some(&service_name),
+                            &call,
+                            &["type"],
+                            err,
+                        ));
+                        continue;
+                    }
+                };
+                if let CallType::Messaging { .. } = parsed.ty {
+                    messaging.push((*service_ndx, parsed));
+                    continue;
+                }
+
+                let Resolution {
+                    service: called_service_ndx,
+                    endpoint: target,
+                    unresolved: reason,
+                } = resolve_call(&graph, &indices, called_name.as_deref(), &parsed);
+                if let (None, Some(name)) = (called_service_ndx, &called_name) {
+                    diagnostics.push(Diagnostic {
+                        kind: DiagnosticKind::UnknownCallee,
+                        object: ObjectKind::Call,
+                        parent: Some(service_name.clone()),
+                        name: Some(name.clone()),
+                        message: format!("No microservice is named {}", name),
+                    });
+                }
+                if let Some(reason) = reason {
+                    unresolved_calls.push(UnresolvedCall {
+                        caller: service_name.clone(),
+                        callee: called_name.clone(),
+                        endpoint: parsed.endpoint.clone(),
+                        reason,
+                    });
+                }
 
-                graph.add_edge(*service_ndx, *called_service_ndx, call);
+                if let Some(called_service_ndx) = called_service_ndx {
+                    parsed.target = target;
+                    graph.add_edge(*service_ndx, called_service_ndx, parsed);
+                }
+            }
+        }
+
+        // Connect every producer to the consumers of the same topic on the same
+        // broker, keeping the producers and consumers without a counterpart
+        for (ndx, call) in messaging.iter() {
+            let (broker, topic, role) = match &call.ty {
+                CallType::Messaging {
+                    broker,
+                    topic,
+                    role,
+                } => (broker, topic, role),
+                _ => continue,
+            };
+            let counterparts: Vec<_> = messaging
+                .iter()
+                .filter(|(_, other)| {
+                    matches!(&other.ty, CallType::Messaging {
+                        broker: other_broker,
+                        topic: other_topic,
+                        role: other_role,
+                    } if other_broker == broker && other_topic == topic && other_role != role)
+                })
+                .collect();
+            if counterparts.is_empty() {
+                let counterpart = match role {
+                    MessagingRole::Producer => "consumes",
+                    MessagingRole::Consumer => "produces",
+                };
+                unresolved_calls.push(UnresolvedCall {
+                    caller: graph[*ndx].name.clone(),
+                    callee: None,
+                    endpoint: call.endpoint.clone(),
+                    reason: format!(
+                        "No microservice {} the {} topic {}",
+                        counterpart, broker, topic
+                    ),
+                });
+            } else if *role == MessagingRole::Producer {
+                for (consumer_ndx, _) in counterparts {
+                    graph.add_edge(*ndx, *consumer_ndx, call.clone());
+                }
             }
         }
 
-        Some(MicroserviceGraph(graph))
+        Some(MicroserviceGraph {
+            graph,
+            unresolved_calls,
+            diagnostics: diagnostics.into_inner(),
+        })
     }
 
     /// Gets the directed edges for the microservice graph
     pub fn edges(&self) -> Edges<Microservice, MicroserviceCall> {
-        Edges::from(&self.0)
+        Edges::from(&self.graph)
     }
 
     // Gets all of the nodes in the graph
     pub fn nodes(&self) -> Vec<Microservice> {
-        get_nodes(&self.0)
+        get_nodes(&self.graph)
+    }
+
+    /// Gets the calls that could not be resolved to a microservice or endpoint
+    pub fn unresolved_calls(&self) -> &[UnresolvedCall] {
+        &self.unresolved_calls
+    }
+
+    /// Gets the ReSSA objects that were skipped while creating the graph
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.diagnostics
     }
 }
 
-fn get_nodes<N: Clone, E>(graph: &DiGraph<N, E>) -> Vec<N> {
-    graph.node_indices().map(|ndx| graph[ndx].clone()).collect()
+/// The microservice and endpoint a call was resolved to
+struct Resolution {
+    service: Option<NodeIndex>,
+    endpoint: Option<Endpoint>,
+    /// Why the call could not be resolved unambiguously, if it could not
+    unresolved: Option<String>,
 }
 
-fn add_nodes<'a, N, E>(
-    graph: &mut DiGraph<N, E>,
-    services: &'a [BTreeMap<String, Value>],
-) -> Vec<NodeIndex>
-where
-    N: TryFrom<&'a BTreeMap<String, Value>>,
-{
-    add_nodes_inner(graph, services.iter().flat_map(N::try_from))
+/// Finds the microservice and endpoint a call is made to, preferring the
+/// endpoint with the most literal path segments in common with the call.
+/// Without the name of the called microservice, the only microservice exposing
+/// the most specific endpoint is used, or the one named after the host of the
+/// call's URL. Several equally specific endpoints are reported as ambiguous
+fn resolve_call(
+    graph: &DiGraph<Microservice, MicroserviceCall>,
+    indices: &[NodeIndex],
+    called_name: Option<&str>,
+    call: &MicroserviceCall,
+) -> Resolution {
+    let called_service_ndx =
+        called_name.and_then(|name| indices.iter().copied().find(|ndx| graph[*ndx].name == name));
+    if let (Some(name), None) = (called_name, called_service_ndx) {
+        return Resolution {
+            service: None,
+            endpoint: None,
+            unresolved: Some(format!("No microservice is named {}", name)),
+        };
+    }
+
+    let (method, url) = match (&call.ty, &call.endpoint) {
+        (CallType::Http(method), Some(url)) => (method, url),
+        _ => {
+            return Resolution {
+                service: called_service_ndx,
+                endpoint: None,
+                unresolved: called_service_ndx
+                    .is_none()
+                    .then(|| "No microservice exposes a matching endpoint".to_string()),
+            }
+        }
+    };
+    let path = &normalize_url(url);
+    let candidates = move |ndx: NodeIndex| {
+        graph[ndx].endpoints.iter().filter_map(move |endpoint| {
+            Some(((ndx, endpoint), endpoint.specificity(method, path)?))
+        })
+    };
+
+    if let Some(ndx) = called_service_ndx {
+        let service = &graph[ndx];
+        let best = most_specific(candidates(ndx));
+        return match &best[..] {
+            [(_, endpoint)] => Resolution {
+                service: Some(ndx),
+                endpoint: Some((*endpoint).clone()),
+                unresolved: None,
+            },
+            // Calls can only be resolved to endpoints if they were detected
+            [] if service.endpoints.is_empty() => Resolution {
+                service: Some(ndx),
+                endpoint: None,
+                unresolved: None,
+            },
+            [] => Resolution {
+                service: Some(ndx),
+                endpoint: None,
+                unresolved: Some(format!("{} exposes no matching endpoint", service.name)),
+            },
+            several => Resolution {
+                service: Some(ndx),
+                endpoint: None,
+                unresolved: Some(ambiguous_endpoints(&service.name, several)),
+            },
+        };
+    }
+
+    let mut best = most_specific(indices.iter().flat_map(|ndx| candidates(*ndx)));
+    if best.len() > 1
+        && best
+            .iter()
+            .any(|(ndx, _)| Some(graph[*ndx].name.as_str()) == url_host(url))
+    {
+        best.retain(|(ndx, _)| Some(graph[*ndx].name.as_str()) == url_host(url));
+    }
+    match &best[..] {
+        [] => Resolution {
+            service: None,
+            endpoint: None,
+            unresolved: Some("No microservice exposes a matching endpoint".to_string()),
+        },
+        [(ndx, endpoint)] => Resolution {
+            service: Some(*ndx),
+            endpoint: Some((*endpoint).clone()),
+            unresolved: None,
+        },
+        several if several.iter().all(|(ndx, _)| *ndx == several[0].0) => Resolution {
+            service: Some(several[0].0),
+            endpoint: None,
+            unresolved: Some(ambiguous_endpoints(&graph[several[0].0].name, several)),
+        },
+        several => {
+            let mut names: Vec<_> = several
+                .iter()
+                .map(|(ndx, _)| graph[*ndx].name.as_str())
+                .collect();
+            names.dedup();
+            Resolution {
+                service: None,
+                endpoint: None,
+                unresolved: Some(format!(
+                    "Several microservices expose equally matching endpoints: {}",
+                    names.join(", ")
+                )),
+            }
+        }
+    }
+}
+
+/// Keeps the candidates with the highest specificity
+fn most_specific<T>(candidates: impl Iterator<Item = (T, usize)>) -> Vec<T> {
+    let candidates: Vec<_> = candidates.collect();
+    let max = candidates.iter().map(|(_, specificity)| *specificity).max();
+    candidates
+        .into_iter()
+        .filter(|(_, specificity)| Some(*specificity) == max)
+        .map(|(candidate, _)| candidate)
+        .collect()
+}
+
+fn ambiguous_endpoints(service: &str, candidates: &[(NodeIndex, &Endpoint)]) -> String {
+    let endpoints: Vec<_> = candidates
+        .iter()
+        .map(|(_, endpoint)| format!("{} {}", endpoint.method, endpoint.path))
+        .collect();
+    format!(
+        "{} exposes several equally matching endpoints: {}",
+        service,
+        endpoints.join(", ")
+    )
+}
+
+/// Lowercases a name and strips any separators from it, so that names such as
+/// `order_item` and `OrderItem` are the same
+pub fn normalize(name: &str) -> String {
+    name.chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+/// Gets the most common value, preferring the first one on ties
+pub fn most_common<T: PartialEq>(values: impl IntoIterator<Item = T>) -> Option<T> {
+    let mut counts: Vec<(T, usize)> = vec![];
+    for value in values {
+        match counts.iter_mut().find(|(counted, _)| *counted == value) {
+            Some((_, count)) => *count += 1,
+            None => counts.push((value, 1)),
+        }
+    }
+
+    // The last of the equally common values is the maximum, so search in reverse
+    counts
+        .into_iter()
+        .rev()
+        .max_by_key(|(_, count)| *count)
+        .map(|(value, _)| value)
+}
+
+fn get_nodes<N: Clone, E>(graph: &DiGraph<N, E>) -> Vec<N> {
+    graph.node_indices().map(|ndx| graph[ndx].clone()).collect()
 }
 
 fn add_nodes_inner<N, E>(
@@ -154,16 +775,30 @@ fn add_nodes_inner<N, E>(
 
 impl AsRef<DiGraph<Microservice, MicroserviceCall>> for MicroserviceGraph {
     fn as_ref(&self) -> &DiGraph<Microservice, MicroserviceCall> {
-        &self.0
+        &self.graph
     }
 }
 
 /// Represents an entity from the ReSSA
-#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Serialize, Deserialize)]
 pub struct Entity {
     pub name: String,
     pub fields: Vec<Field>,
     pub ty: DatabaseType,
+    /// The microservice entities this entity was created or merged from
+    #[serde(default)]
+    pub sources: Vec<EntitySource>,
+}
+
+/// A microservice entity that an [`Entity`] was created or merged from
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Serialize, Deserialize)]
+pub struct EntitySource {
+    /// The name of the microservice owning the entity, if it is known
+    pub microservice: Option<String>,
+    /// The name of the entity in the microservice
+    pub entity: String,
+    /// The database the microservice stores the entity in
+    pub ty: DatabaseType,
 }
 
 impl Entity {
@@ -172,50 +807,199 @@ impl Entity {
             name: name.to_string(),
             fields,
             ty,
+            sources: vec![],
         }
     }
-}
 
-impl TryFrom<&BTreeMap<String, Value>> for Entity {
-    type Error = ressa::Error;
+    /// Creates an entity merged from others, stored in the most common
+    /// database of its sources
+    pub fn merged(name: impl ToString, fields: Vec<Field>, sources: Vec<EntitySource>) -> Self {
+        let ty = most_common(sources.iter().map(|source| &source.ty))
+            .cloned()
+            .unwrap_or_else(|| DatabaseType::Unknown(String::new()));
 
-    /// Attempts to create an Entity from a ReSSA object
-    fn try_from(entity: &BTreeMap<String, Value>) -> Result<Self, Self::Error> {
+        Entity {
+            sources,
+            ..Entity::new(name, fields, ty)
+        }
+    }
+
+    /// Gets the microservice entities this entity was created from, which is
+    /// the entity itself if none were recorded
+    pub fn source_entities(&self) -> Vec<EntitySource> {
+        if self.sources.is_empty() {
+            vec![EntitySource {
+                microservice: None,
+                entity: self.name.clone(),
+                ty: self.ty.clone(),
+            }]
+        } else {
+            self.sources.clone()
+        }
+    }
+
+    /// Whether the microservice owns one of the sources of the entity
+    pub fn is_owned_by(&self, microservice: &str) -> bool {
+        self.sources
+            .iter()
+            .any(|source| source.microservice.as_deref() == Some(microservice))
+    }
+
+    /// Gets the distinct databases the sources of the entity are stored in, if
+    /// they are not all stored in the same database
+    pub fn conflicting_types(&self) -> Vec<&DatabaseType> {
+        let mut types: Vec<&DatabaseType> = vec![];
+        for source in self.sources.iter() {
+            if !types.contains(&&source.ty) {
+                types.push(&source.ty);
+            }
+        }
+        if types.len() > 1 {
+            types
+        } else {
+            vec![]
+        }
+    }
+
+    /// Attempts to create an Entity from a ReSSA object, recording the fields
+    /// that had to be skipped
+    pub fn from_ressa(
+        entity: &BTreeMap<String, Value>,
+        diagnostics: &mut Diagnostics,
+    ) -> Result<Self, ressa::Error> {
         let name = ressa::extract(entity, "name", Value::into_string)?;
         let ty: DatabaseType = ressa::extract(entity, "type", Value::into_string)?.into();
 
         let fields = ressa::extract_vec(entity, "fields", Value::into_object)?
             .into_iter()
-            .map(ressa::extract_object)
-            .flat_map(|f| Field::try_from(&f))
-            .collect::<Vec<_>>();
+            .map(ressa::extract_object);
+        let fields = diagnostics.collect(
+            fields,
+            ObjectKind::Field,
+            Some(&name),
+            &["name", "type", "is_collection"],
+            |field, _| Field::try_from(field),
+        );
 
-        Ok(Entity { name, fields, ty })
+        Ok(Entity::new(name, fields, ty))
     }
 }
 
-#[derive(Debug, PartialEq, Eq, Hash, Clone, Display)]
+impl TryFrom<&BTreeMap<String, Value>> for Entity {
+    type Error = ressa::Error;
+
+    /// Attempts to create an Entity from a ReSSA object
+    fn try_from(entity: &BTreeMap<String, Value>) -> Result<Self, Self::Error> {
+        Entity::from_ressa(entity, &mut Diagnostics::default())
+    }
+}
+
+/// The database an entity is stored in, serialized as its name
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Serialize, Deserialize)]
+#[serde(from = "String", into = "String")]
 pub enum DatabaseType {
     MySQL,
+    PostgreSQL,
+    H2,
     MongoDB,
+    Redis,
+    DynamoDB,
+    Cassandra,
+    Neo4j,
+    Elasticsearch,
     Unknown(String),
 }
 
+impl DatabaseType {
+    /// Gets the kind of database
+    pub fn category(&self) -> DatabaseCategory {
+        use DatabaseType::*;
+        match self {
+            MySQL | PostgreSQL | H2 => DatabaseCategory::Relational,
+            MongoDB => DatabaseCategory::Document,
+            // Cassandra is a wide-column store, which is a partitioned key-value store
+            Redis | DynamoDB | Cassandra => DatabaseCategory::KeyValue,
+            Neo4j => DatabaseCategory::Graph,
+            Elasticsearch => DatabaseCategory::Search,
+            Unknown(_) => DatabaseCategory::Unknown,
+        }
+    }
+}
+
+impl std::fmt::Display for DatabaseType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        use DatabaseType::*;
+        let name = match self {
+            MySQL => "MySQL",
+            PostgreSQL => "PostgreSQL",
+            H2 => "H2",
+            MongoDB => "MongoDB",
+            Redis => "Redis",
+            DynamoDB => "DynamoDB",
+            Cassandra => "Cassandra",
+            Neo4j => "Neo4j",
+            Elasticsearch => "Elasticsearch",
+            Unknown(name) => name,
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl From<DatabaseType> for String {
+    fn from(ty: DatabaseType) -> Self {
+        ty.to_string()
+    }
+}
+
 impl From<String> for DatabaseType {
+    /// Parses the name of a database, ignoring case and separators and
+    /// accepting common aliases such as `postgres` and `mongo`
     fn from(value: String) -> Self {
-        match &*value {
-            "MySQL" => DatabaseType::MySQL,
-            "MongoDB" => DatabaseType::MongoDB,
+        match &*normalize(&value) {
+            "mysql" | "mariadb" => DatabaseType::MySQL,
+            "postgresql" | "postgres" | "pgsql" | "pg" => DatabaseType::PostgreSQL,
+            "h2" | "h2database" => DatabaseType::H2,
+            "mongodb" | "mongo" => DatabaseType::MongoDB,
+            "redis" => DatabaseType::Redis,
+            "dynamodb" | "dynamo" => DatabaseType::DynamoDB,
+            "cassandra" => DatabaseType::Cassandra,
+            "neo4j" => DatabaseType::Neo4j,
+            "elasticsearch" | "elastic" | "opensearch" => DatabaseType::Elasticsearch,
             _ => DatabaseType::Unknown(value),
         }
     }
 }
 
-#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+/// The kind of a database, by how it models its data
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Display, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DatabaseCategory {
+    Relational,
+    Document,
+    #[strum(serialize = "Key-Value")]
+    KeyValue,
+    Graph,
+    Search,
+    Unknown,
+}
+
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Serialize, Deserialize)]
 pub struct Field {
     pub name: String,
     pub ty: String,
     pub is_collection: bool,
+    /// Whether the field identifies its entity
+    #[serde(default)]
+    pub is_id: bool,
+    /// Whether the field may be empty, if known
+    #[serde(default)]
+    pub nullable: Option<bool>,
+    /// Whether no two entities may have the same value for the field
+    #[serde(default)]
+    pub is_unique: bool,
+    /// The raw annotations or decorators of the field, such as `@Column(name = "id")`
+    #[serde(default)]
+    pub annotations: Vec<String>,
 }
 
 impl Field {
@@ -224,44 +1008,102 @@ impl Field {
             name: name.to_string(),
             ty: ty.to_string(),
             is_collection,
+            is_id: false,
+            nullable: None,
+            is_unique: false,
+            annotations: vec![],
         }
     }
+
+    /// Combines the metadata of the fields that were merged into this one, where
+    /// the field may be empty if any of them may be
+    pub fn merge_metadata<'a>(mut self, fields: impl IntoIterator<Item = &'a Field>) -> Self {
+        for field in fields {
+            self.is_id |= field.is_id;
+            self.is_unique |= field.is_unique;
+            self.nullable = match (self.nullable, field.nullable) {
+                (Some(a), Some(b)) => Some(a || b),
+                (a, b) => a.or(b),
+            };
+            for annotation in field.annotations.iter() {
+                if !self.annotations.contains(annotation) {
+                    self.annotations.push(annotation.clone());
+                }
+            }
+        }
+        self
+    }
 }
 
 impl TryFrom<&BTreeMap<String, Value>> for Field {
     type Error = ressa::Error;
 
+    /// Attempts to create a field from a ReSSA object with a `name`, `type` and
+    /// `is_collection`, and the optional `annotations` (comma or `@` separated),
+    /// `is_id`, `nullable` and `is_unique`, which override what the annotations imply
     fn try_from(entity: &BTreeMap<String, Value>) -> Result<Self, Self::Error> {
         let name = ressa::extract(entity, "name", Value::into_string)?;
         let ty = ressa::extract(entity, "type", Value::into_string)?;
         let is_collection = ressa::extract_primitive(entity, "is_collection", Value::into_bool)?;
-        Ok(Field {
-            name,
-            ty,
-            is_collection,
-        })
-    }
-}
 
-#[derive(Debug, Clone, Copy)]
-pub enum Cardinality {
-    One,
-    Many,
-}
-
-impl ToString for Cardinality {
-    fn to_string(&self) -> String {
-        use Cardinality::*;
-        match self {
-            One => "1",
-            Many => "*",
+        let annotations = ressa::extract(entity, "annotations", Value::into_string)
+            .map(|annotations| split_annotations(&annotations))
+            .unwrap_or_default();
+        let mut field = Field::new(name, ty, is_collection).with_annotations(annotations);
+        let flag = |key: &str| ressa::extract_primitive(entity, key, Value::into_bool).ok();
+        if let Some(is_id) = flag("is_id") {
+            field.is_id = is_id;
+        }
+        if let Some(nullable) = flag("nullable") {
+            field.nullable = Some(nullable);
         }
-        .to_string()
+        if let Some(is_unique) = flag("is_unique") {
+            field.is_unique = is_unique;
+        }
+        Ok(field)
     }
 }
 
-#[derive(Debug, Clone)]
-pub struct EntityGraph(DiGraph<Entity, Cardinality>);
+/// A graph of the relationships between entities
+///
+/// The serialized representation in JSON lists the entities as nodes and the
+/// relationships as edges between the indices of the nodes
+/// ```json
+/// {
+///   "nodes": [
+///     {
+///       "name": "Order",
+///       "fields": [
+///         {
+///           "name": "items",
+///           "ty": "OrderItem",
+///           "is_collection": true,
+///           "is_id": false,
+///           "nullable": null,
+///           "is_unique": false,
+///           "annotations": ["@OneToMany"]
+///         }
+///       ],
+///       "ty": "MySQL",
+///       "sources": [{ "microservice": "orders", "entity": "Order", "ty": "MySQL" }]
+///     },
+///     { "name": "OrderItem", "fields": [], "ty": "MySQL", "sources": [] }
+///   ],
+///   "edges": [
+///     {
+///       "from": 0,
+///       "to": 1,
+///       "weight": { "source": "one", "target": "many", "field": "items", "inverse_field": null }
+///     }
+///   ]
+/// }
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(
+    into = "GraphRepr<Entity, Relationship>",
+    try_from = "GraphRepr<Entity, Relationship>"
+)]
+pub struct EntityGraph(DiGraph<Entity, Relationship>);
 
 impl EntityGraph {
     /// Attempts to create an entity graph from a list of combined Entities
@@ -269,36 +1111,65 @@ impl EntityGraph {
         let mut graph = DiGraph::new();
         let indices = add_nodes_inner(&mut graph, entities.iter().cloned());
 
-        // Add entity nodes to the graph
+        // Find the fields of every entity referring to another entity
+        let mut references = vec![];
         for entity in entities {
             let entity_ndx = indices
                 .iter()
                 .find(|ndx| graph[**ndx].name == entity.name)?;
 
             for field in entity.fields.iter() {
-                // Get the matching entity for the field
-                let other_entity_ndx = indices.iter().find(|ndx| graph[**ndx].name == field.ty);
+                // Get the matching entity for the type the field refers to, or else
+                // for the relationship its annotations declare, such as a foreign key
+                let ty = TypeExpr::parse(&field.ty);
+                let declared = field.declared_relationship();
+                let other_entity_ndx = indices
+                    .iter()
+                    .find(|ndx| graph[**ndx].name == ty.name)
+                    .or_else(|| {
+                        declared.as_ref()?.targets.iter().find_map(|target| {
+                            indices
+                                .iter()
+                                .find(|ndx| *target == normalize(&graph[**ndx].name))
+                        })
+                    });
                 let other_entity_ndx = match other_entity_ndx {
                     Some(ndx) => ndx,
                     _ => continue,
                 };
 
-                let other_cardinality = if field.is_collection {
+                let declared_cardinality = declared.as_ref().and_then(|d| d.cardinality);
+                let cardinality = if field.is_collection
+                    || ty.is_collection
+                    || matches!(declared_cardinality, Some(c) if c.is_many())
+                {
                     Cardinality::Many
+                } else if ty.is_optional || field.nullable == Some(true) {
+                    Cardinality::ZeroOrOne
                 } else {
                     Cardinality::One
                 };
 
-                graph.add_edge(*entity_ndx, *other_entity_ndx, other_cardinality);
-                //graph.add_edge(*other_entity_ndx, *entity_ndx, Cardinality::One);
+                references.push(Reference {
+                    from: *entity_ndx,
+                    to: *other_entity_ndx,
+                    field: field.name.clone(),
+                    cardinality,
+                    inverse: declared.and_then(|declared| declared.inverse),
+                });
             }
         }
 
+        // Entities referring to each other share a single relationship
+        for (from, to, relationship) in pair_references(&references) {
+            graph.add_edge(from, to, relationship);
+        }
+
         Some(EntityGraph(graph))
     }
 
     /// Gets the directed edges for the entity graph
-    pub fn edges(&self) -> Edges<Entity, Cardinality> {
+    pub fn edges(&self) -> Edges<Entity, Relationship> {
         Edges::from(&self.0)
     }
 
@@ -307,6 +1178,12 @@ impl EntityGraph {
         get_nodes(&self.0)
     }
 
+    /// Filters an entity graph to only contain the entities owned by the microservice
+    pub fn filter_owner(&mut self, microservice: &str) {
+        self.0
+            .retain_nodes(|graph, ndx| graph[ndx].is_owned_by(microservice));
+    }
+
     /// Filters an entity graph to contain certain entities
     pub fn filter_entities(&mut self, entities: &[Entity]) {
         let graph = &mut self.0;
@@ -327,8 +1204,8 @@ impl EntityGraph {
     }
 }
 
-impl AsRef<DiGraph<Entity, Cardinality>> for EntityGraph {
-    fn as_ref(&self) -> &DiGraph<Entity, Cardinality> {
+impl AsRef<DiGraph<Entity, Relationship>> for EntityGraph {
+    fn as_ref(&self) -> &DiGraph<Entity, Relationship> {
         &self.0
     }
 }
@@ -372,3 +1249,371 @@ where
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use runestick::Shared;
+    use test_case::test_case;
+
+    use super::*;
+
+    #[test_case("MySQL" => (DatabaseType::MySQL, DatabaseCategory::Relational) ; "mysql")]
+    #[test_case("mariadb" => (DatabaseType::MySQL, DatabaseCategory::Relational) ; "mariadb")]
+    #[test_case("postgres" => (DatabaseType::PostgreSQL, DatabaseCategory::Relational) ; "postgres")]
+    #[test_case("PG_SQL" => (DatabaseType::PostgreSQL, DatabaseCategory::Relational) ; "pgsql")]
+    #[test_case("mongo" => (DatabaseType::MongoDB, DatabaseCategory::Document) ; "mongo")]
+    #[test_case("Dynamo-DB" => (DatabaseType::DynamoDB, DatabaseCategory::KeyValue) ; "dynamodb")]
+    #[test_case("Cassandra" => (DatabaseType::Cassandra, DatabaseCategory::KeyValue) ; "cassandra")]
+    #[test_case("neo4j" => (DatabaseType::Neo4j, DatabaseCategory::Graph) ; "neo4j")]
+    #[test_case("Elastic" => (DatabaseType::Elasticsearch, DatabaseCategory::Search) ; "elastic")]
+    #[test_case("Couch DB" => (DatabaseType::Unknown("Couch DB".into()), DatabaseCategory::Unknown) ; "unknown")]
+    fn parse_database_type(name: &str) -> (DatabaseType, DatabaseCategory) {
+        let ty = DatabaseType::from(name.to_string());
+        let category = ty.category();
+        (ty, category)
+    }
+
+    #[test]
+    fn prefer_first_most_common() {
+        assert_eq!(Some("b"), most_common(["a", "b", "c", "b"]));
+        assert_eq!(Some("a"), most_common(["a", "b", "b", "a"]));
+        assert_eq!(None, most_common(Vec::<&str>::new()));
+    }
+
+    fn string(value: &str) -> Value {
+        Value::String(Shared::new(value.to_string()))
+    }
+
+    fn object(entries: Vec<(&str, Value)>) -> Value {
+        let mut object = runestick::Object::new();
+        for (key, value) in entries {
+            object.insert(key.to_string(), value);
+        }
+        Value::Object(Shared::new(object))
+    }
+
+    fn list(values: Vec<Value>) -> Value {
+        Value::Vec(Shared::new(runestick::Vec::from(values)))
+    }
+
+    fn ressa(services: Vec<Value>) -> RessaResult {
+        let ctx = vec![("services".to_string(), list(services))]
+            .into_iter()
+            .collect();
+        vec![("ctx".to_string(), ctx)].into_iter().collect()
+    }
+
+    fn service(name: &str, endpoints: &[&str], calls: Vec<Value>) -> Value {
+        object(vec![
+            ("name", string(name)),
+            ("language", string("Java")),
+            ("entities", list(vec![])),
+            (
+                "endpoints",
+                list(endpoints.iter().map(|endpoint| string(endpoint)).collect()),
+            ),
+            ("calls", list(calls)),
+        ])
+    }
+
+    fn http_call(method: &str, endpoint: &str) -> Value {
+        object(vec![
+            ("type", string("HTTP")),
+            ("method", string(method)),
+            ("endpoint", string(endpoint)),
+        ])
+    }
+
+    fn messaging_call(broker: &str, topic: &str, role: &str) -> Value {
+        object(vec![
+            ("type", string("MESSAGING")),
+            ("broker", string(broker)),
+            ("topic", string(topic)),
+            ("role", string(role)),
+        ])
+    }
+
+    #[test]
+    fn read_call_metadata() {
+        let call: BTreeMap<_, _> = vec![
+            ("type", string("HTTP")),
+            ("method", string("GET")),
+            ("endpoint", string("/api/v1/orders/{}")),
+            ("calling_method", string("OrderClient.getOrder")),
+            ("endpoint_function", string("")),
+            ("arguments", string("String, Long,")),
+            ("return_type", string("Order")),
+            ("file", string("src/OrderClient.java")),
+            ("line", Value::Integer(42)),
+        ]
+        .into_iter()
+        .map(|(key, value)| (key.to_string(), value))
+        .collect();
+
+        let call = MicroserviceCall::try_from(&call).unwrap();
+        assert!(matches!(call.ty, CallType::Http(method) if method == http::Method::GET));
+        assert_eq!(Some("/api/v1/orders/{}"), call.endpoint.as_deref());
+        assert_eq!(Some("OrderClient.getOrder"), call.calling_method.as_deref());
+        assert_eq!(None, call.endpoint_function);
+        assert_eq!(vec!["String", "Long"], call.arguments);
+        assert_eq!(Some("Order"), call.return_type.as_deref());
+        assert_eq!(
+            Some(SourceLocation {
+                file: "src/OrderClient.java".into(),
+                line: Some(42)
+            }),
+            call.location
+        );
+    }
+
+    #[test]
+    fn resolve_calls_to_most_specific_endpoint() {
+        let result = ressa(vec![
+            service(
+                "orders",
+                &["GET /api/orders/{id} Order", "GET /api/orders/latest Order"],
+                vec![],
+            ),
+            service("users", &["GET /api/users/{id} User"], vec![]),
+            service(
+                "frontend",
+                &[],
+                vec![
+                    http_call("GET", "http://orders:8080/api/orders/latest"),
+                    http_call("GET", "/api/users/${id}"),
+                    http_call("POST", "/api/payments"),
+                ],
+            ),
+        ]);
+
+        let graph = MicroserviceGraph::try_new(&result).unwrap();
+        let edges: Vec<_> = graph
+            .edges()
+            .into_inner()
+            .into_iter()
+            .map(|edge| {
+                let target = edge.weight.target.map(|endpoint| endpoint.path);
+                (edge.from.name, edge.to.name, target)
+            })
+            .collect();
+        assert_eq!(
+            vec![
+                (
+                    "frontend".to_string(),
+                    "orders".to_string(),
+                    Some("/api/orders/latest".to_string())
+                ),
+                (
+                    "frontend".to_string(),
+                    "users".to_string(),
+                    Some("/api/users/{}".to_string())
+                ),
+            ],
+            edges
+        );
+        assert_eq!(
+            &[UnresolvedCall {
+                caller: "frontend".into(),
+                callee: None,
+                endpoint: Some("/api/payments".into()),
+                reason: "No microservice exposes a matching endpoint".into(),
+            }],
+            graph.unresolved_calls()
+        );
+    }
+
+    #[test]
+    fn report_ambiguous_endpoints() {
+        let result = ressa(vec![
+            service(
+                "orders",
+                &["GET /api/{kind}/latest", "GET /api/orders/{id}"],
+                vec![],
+            ),
+            service(
+                "frontend",
+                &[],
+                vec![object(vec![
+                    ("type", string("HTTP")),
+                    ("method", string("GET")),
+                    ("endpoint", string("/api/orders/latest")),
+                    ("name", string("orders")),
+                ])],
+            ),
+        ]);
+
+        let graph = MicroserviceGraph::try_new(&result).unwrap();
+        let edges = graph.edges().into_inner();
+        assert_eq!(1, edges.len());
+        assert_eq!(None, edges[0].weight.target);
+        assert_eq!(1, graph.unresolved_calls().len());
+        assert_eq!(
+            "orders exposes several equally matching endpoints: \
+             GET /api/{}/latest, GET /api/orders/{}",
+            graph.unresolved_calls()[0].reason
+        );
+    }
+
+    #[test]
+    fn skip_broken_objects() {
+        let field = |name: &str, is_collection: Value| {
+            object(vec![
+                ("name", string(name)),
+                ("type", string("Long")),
+                ("is_collection", is_collection),
+            ])
+        };
+        let order = object(vec![
+            ("name", string("Order")),
+            ("type", string("MySQL")),
+            (
+                "fields",
+                list(vec![
+                    field("id", Value::Bool(false)),
+                    field("total", string("no")),
+                ]),
+            ),
+        ]);
+        let orders = object(vec![
+            ("name", string("orders")),
+            ("language", string("Java")),
+            ("entities", list(vec![order])),
+            (
+                "calls",
+                list(vec![
+                    http_call("NOT A METHOD", "/api/users"),
+                    object(vec![
+                        ("type", string("HTTP")),
+                        ("method", string("GET")),
+                        ("endpoint", string("/api/bills")),
+                        ("name", string("billing")),
+                    ]),
+                    http_call("GET", "/api/users/{}"),
+                ]),
+            ),
+        ]);
+        let broken = object(vec![("name", string("broken")), ("entities", list(vec![]))]);
+        let result = ressa(vec![
+            orders,
+            broken,
+            service("users", &["GET /api/users/{id} User"], vec![]),
+        ]);
+
+        let graph = MicroserviceGraph::try_new(&result).unwrap();
+        let nodes = graph.nodes();
+        let names: Vec<_> = nodes.iter().map(|ms| ms.name.as_str()).collect();
+        assert_eq!(vec!["orders", "users"], names);
+        let fields: Vec<_> = nodes[0].ref_entities[0]
+            .fields
+            .iter()
+            .map(|field| field.name.as_str())
+            .collect();
+        assert_eq!(vec!["id"], fields);
+        assert_eq!(1, graph.edges().into_inner().len());
+
+        let diagnostics: Vec<_> = graph
+            .diagnostics()
+            .iter()
+            .map(|diagnostic| {
+                (
+                    diagnostic.object,
+                    diagnostic.kind,
+                    diagnostic.parent.as_deref(),
+                    diagnostic.name.as_deref(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            vec![
+                (
+                    ObjectKind::Field,
+                    DiagnosticKind::Malformed,
+                    Some("Order"),
+                    Some("total")
+                ),
+                (
+                    ObjectKind::Service,
+                    DiagnosticKind::MissingKey,
+                    None,
+                    Some("broken")
+                ),
+                (
+                    ObjectKind::Call,
+                    DiagnosticKind::BadHttpMethod,
+                    Some("orders"),
+                    None
+                ),
+                (
+                    ObjectKind::Call,
+                    DiagnosticKind::UnknownCallee,
+                    Some("orders"),
+                    Some("billing")
+                ),
+            ],
+            diagnostics
+        );
+    }
+
+    #[test]
+    fn connect_producers_to_consumers() {
+        let result = ressa(vec![
+            service(
+                "orders",
+                &[],
+                vec![
+                    messaging_call("kafka", "orders", "producer"),
+                    messaging_call("kafka", "refunds", "producer"),
+                ],
+            ),
+            service(
+                "shipping",
+                &[],
+                vec![messaging_call("Kafka", "orders", "consumer")],
+            ),
+            service(
+                "billing",
+                &[],
+                vec![
+                    messaging_call("rabbitmq", "orders", "consumer"),
+                    messaging_call("kafka", "invoices", "consumer"),
+                ],
+            ),
+        ]);
+
+        let graph = MicroserviceGraph::try_new(&result).unwrap();
+        let edges = graph.edges().into_inner();
+        assert_eq!(1, edges.len());
+        assert_eq!(
+            ("orders", "shipping"),
+            (&*edges[0].from.name, &*edges[0].to.name)
+        );
+        assert!(matches!(
+            &edges[0].weight.ty,
+            CallType::Messaging {
+                broker: Broker::Kafka,
+                topic,
+                role: MessagingRole::Producer,
+            } if topic == "orders"
+        ));
+
+        let unresolved: Vec<_> = graph
+            .unresolved_calls()
+            .iter()
+            .map(|call| (call.caller.as_str(), call.reason.as_str()))
+            .collect();
+        assert_eq!(
+            vec![
+                ("orders", "No microservice consumes the Kafka topic refunds"),
+                (
+                    "billing",
+                    "No microservice produces the RabbitMQ topic orders"
+                ),
+                (
+                    "billing",
+                    "No microservice produces the Kafka topic invoices"
+                ),
+            ],
+            unresolved
+        );
+    }
+}
@@ -0,0 +1,173 @@
+use std::collections::BTreeSet;
+
+use petgraph::{algo::tarjan_scc, graph::NodeIndex, visit::EdgeRef};
+use serde::{Deserialize, Serialize};
+
+use crate::MicroserviceGraph;
+
+/// The architecture metrics of a microservice system
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Metrics {
+    /// The metrics of every microservice, in the order of the graph's nodes
+    pub services: Vec<ServiceMetrics>,
+    /// The metrics of the system as a whole
+    pub system: SystemMetrics,
+}
+
+/// The coupling metrics of a single microservice. Only distinct microservices
+/// are counted, so calling another microservice twice or calling itself does
+/// not increase its coupling
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ServiceMetrics {
+    /// The name of the microservice
+    pub name: String,
+    /// The absolute importance of the service (AIS), the number of
+    /// microservices calling it
+    pub ais: usize,
+    /// The absolute dependence of the service (ADS), the number of
+    /// microservices it calls
+    pub ads: usize,
+    /// The absolute criticality of the service (ACS), `ais * ads`
+    pub acs: usize,
+    /// The number of microservices it is coupled to in either direction, `ais + ads`
+    pub coupling: usize,
+    /// How much the microservice depends on others rather than the other way
+    /// around, `ads / (ais + ads)` from 0 (stable) to 1 (unstable)
+    pub instability: f64,
+}
+
+/// The metrics of a microservice system as a whole
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SystemMetrics {
+    /// The number of microservices
+    pub service_count: usize,
+    /// The number of distinct pairs of microservices where one calls the other
+    pub dependency_count: usize,
+    /// The number of dependencies out of all possible dependencies, from 0 to 1
+    pub density: f64,
+    /// The microservices along the longest chain of calls. Microservices calling
+    /// each other in a cycle are all part of the chain through it
+    pub longest_call_chain: Vec<String>,
+    /// The groups of microservices that can all reach each other through calls,
+    /// leaving out microservices that are not part of a cycle
+    pub strongly_connected_components: Vec<Vec<String>>,
+}
+
+impl MicroserviceGraph {
+    /// Computes the architecture metrics of the microservice system
+    pub fn metrics(&self) -> Metrics {
+        let graph = &self.graph;
+
+        // Only count distinct dependencies between different microservices
+        let dependencies: BTreeSet<(NodeIndex, NodeIndex)> = graph
+            .edge_references()
+            .map(|edge| (edge.source(), edge.target()))
+            .filter(|(from, to)| from != to)
+            .collect();
+
+        let services = graph
+            .node_indices()
+            .map(|ndx| {
+                let ais = dependencies.iter().filter(|(_, to)| *to == ndx).count();
+                let ads = dependencies.iter().filter(|(from, _)| *from == ndx).count();
+                let coupling = ais + ads;
+                ServiceMetrics {
+                    name: graph[ndx].name.clone(),
+                    ais,
+                    ads,
+                    acs: ais * ads,
+                    coupling,
+                    instability: if coupling == 0 {
+                        0.0
+                    } else {
+                        ads as f64 / coupling as f64
+                    },
+                }
+            })
+            .collect();
+
+        let service_count = graph.node_count();
+        let possible = service_count * service_count.saturating_sub(1);
+        let density = if possible == 0 {
+            0.0
+        } else {
+            dependencies.len() as f64 / possible as f64
+        };
+
+        // Tarjan's algorithm finds the components in reverse topological order,
+        // so the components a component calls are always visited before it
+        let components = tarjan_scc(graph);
+        let mut component_of = vec![0; service_count];
+        for (i, component) in components.iter().enumerate() {
+            for ndx in component {
+                component_of[ndx.index()] = i;
+            }
+        }
+
+        // The longest chain starting at every component, and the component it continues in
+        let mut longest: Vec<(usize, Option<usize>)> = Vec::with_capacity(components.len());
+        for (i, component) in components.iter().enumerate() {
+            let next = dependencies
+                .iter()
+                .filter(|(from, _)| component_of[from.index()] == i)
+                .map(|(_, to)| component_of[to.index()])
+                .filter(|to| *to != i)
+                .max_by_key(|to| longest[*to].0);
+            let length = component.len() + next.map_or(0, |next| longest[next].0);
+            longest.push((length, next));
+        }
+
+        let mut longest_call_chain = vec![];
+        let mut current = (0..components.len()).max_by_key(|i| longest[*i].0);
+        while let Some(i) = current {
+            longest_call_chain.extend(components[i].iter().map(|ndx| graph[*ndx].name.clone()));
+            current = longest[i].1;
+        }
+
+        let strongly_connected_components = components
+            .iter()
+            .filter(|component| component.len() > 1)
+            .map(|component| {
+                component
+                    .iter()
+                    .map(|ndx| graph[*ndx].name.clone())
+                    .collect()
+            })
+            .collect();
+
+        Metrics {
+            services,
+            system: SystemMetrics {
+                service_count,
+                dependency_count: dependencies.len(),
+                density,
+                longest_call_chain,
+                strongly_connected_components,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{testing::microservice_graph, CallType, MicroserviceCall};
+
+    #[test]
+    fn compute_metrics() {
+        let calls = [(0, 1), (0, 1), (1, 2), (2, 1), (2, 2)]
+            .map(|(from, to)| (from, to, MicroserviceCall::new(CallType::Rpc)));
+        let graph = microservice_graph(&["a", "b", "c", "d"], calls);
+
+        let metrics = graph.metrics();
+        let b = &metrics.services[1];
+        assert_eq!((2, 1, 2, 3), (b.ais, b.ads, b.acs, b.coupling));
+        assert_eq!(1.0, metrics.services[0].instability);
+        assert_eq!(0.0, metrics.services[3].instability);
+
+        assert_eq!(3, metrics.system.dependency_count);
+        assert_eq!(0.25, metrics.system.density);
+        assert_eq!(3, metrics.system.longest_call_chain.len());
+        assert_eq!("a", metrics.system.longest_call_chain[0]);
+        assert_eq!(1, metrics.system.strongly_connected_components.len());
+    }
+}
@@ -0,0 +1,185 @@
+use petgraph::graph::NodeIndex;
+use serde::{Deserialize, Serialize};
+
+/// How many entities one side of a relationship is associated with
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Cardinality {
+    ZeroOrOne,
+    One,
+    /// Zero or more
+    Many,
+    OneOrMany,
+}
+
+impl Cardinality {
+    /// Whether the side can be associated with more than one entity
+    pub fn is_many(&self) -> bool {
+        matches!(self, Cardinality::Many | Cardinality::OneOrMany)
+    }
+
+    /// Whether the side can be associated with no entity
+    pub fn is_optional(&self) -> bool {
+        matches!(self, Cardinality::ZeroOrOne | Cardinality::Many)
+    }
+}
+
+impl ToString for Cardinality {
+    fn to_string(&self) -> String {
+        use Cardinality::*;
+        match self {
+            ZeroOrOne => "0..1",
+            One => "1",
+            Many => "*",
+            OneOrMany => "1..*",
+        }
+        .to_string()
+    }
+}
+
+/// The kind of an association, by whether each side is one or many
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RelationshipKind {
+    OneToOne,
+    OneToMany,
+    ManyToOne,
+    ManyToMany,
+}
+
+/// An association from a source entity to a target entity
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Relationship {
+    /// How many source entities a target entity is associated with
+    pub source: Cardinality,
+    /// How many target entities a source entity is associated with
+    pub target: Cardinality,
+    /// The field of the source entity referring to the target
+    pub field: String,
+    /// The field of the target entity referring back to the source, if the
+    /// relationship is bidirectional
+    pub inverse_field: Option<String>,
+}
+
+impl Relationship {
+    /// Gets the kind of the association
+    pub fn kind(&self) -> RelationshipKind {
+        match (self.source.is_many(), self.target.is_many()) {
+            (false, false) => RelationshipKind::OneToOne,
+            (false, true) => RelationshipKind::OneToMany,
+            (true, false) => RelationshipKind::ManyToOne,
+            (true, true) => RelationshipKind::ManyToMany,
+        }
+    }
+
+    /// Whether both entities refer to each other
+    pub fn is_bidirectional(&self) -> bool {
+        self.inverse_field.is_some()
+    }
+}
+
+/// A field of an entity referring to another entity
+#[derive(Debug, Clone)]
+pub(crate) struct Reference {
+    pub(crate) from: NodeIndex,
+    pub(crate) to: NodeIndex,
+    pub(crate) field: String,
+    pub(crate) cardinality: Cardinality,
+    /// How many entities refer to the same entity, if declared by the field
+    pub(crate) inverse: Option<Cardinality>,
+}
+
+/// Pairs up the references between the same two entities in opposite directions,
+/// turning every pair and every remaining reference into a single relationship
+pub(crate) fn pair_references(
+    references: &[Reference],
+) -> Vec<(NodeIndex, NodeIndex, Relationship)> {
+    let mut paired = vec![false; references.len()];
+    let mut relationships = vec![];
+
+    for (ndx, reference) in references.iter().enumerate() {
+        if paired[ndx] {
+            continue;
+        }
+        paired[ndx] = true;
+
+        let inverse = (0..references.len()).find(|&other| {
+            !paired[other]
+                && references[other].from == reference.to
+                && references[other].to == reference.from
+        });
+        let relationship = match inverse {
+            Some(inverse) => {
+                paired[inverse] = true;
+                let inverse = &references[inverse];
+                Relationship {
+                    source: inverse.cardinality,
+                    target: reference.cardinality,
+                    field: reference.field.clone(),
+                    inverse_field: Some(inverse.field.clone()),
+                }
+            }
+            // Without a way back or a declared inverse, a collection is assumed to be owned
+            // by its entity, while any number of entities may refer to the same single entity
+            None => Relationship {
+                source: match reference.inverse {
+                    Some(inverse) => inverse,
+                    None if reference.cardinality.is_many() => Cardinality::One,
+                    None => Cardinality::Many,
+                },
+                target: reference.cardinality,
+                field: reference.field.clone(),
+                inverse_field: None,
+            },
+        };
+        relationships.push((reference.from, reference.to, relationship));
+    }
+
+    relationships
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reference(from: usize, to: usize, field: &str, cardinality: Cardinality) -> Reference {
+        Reference {
+            from: NodeIndex::new(from),
+            to: NodeIndex::new(to),
+            field: field.to_string(),
+            cardinality,
+            inverse: None,
+        }
+    }
+
+    #[test]
+    fn pair_bidirectional_references() {
+        let relationships = pair_references(&[
+            reference(0, 1, "items", Cardinality::Many),
+            reference(1, 0, "order", Cardinality::One),
+            reference(1, 2, "tags", Cardinality::Many),
+            reference(2, 1, "items", Cardinality::Many),
+            reference(2, 2, "parent", Cardinality::ZeroOrOne),
+        ]);
+        let kinds: Vec<_> = relationships
+            .iter()
+            .map(|(from, to, relationship)| {
+                (
+                    from.index(),
+                    to.index(),
+                    relationship.kind(),
+                    relationship.is_bidirectional(),
+                )
+            })
+            .collect();
+
+        assert_eq!(
+            kinds,
+            vec![
+                (0, 1, RelationshipKind::OneToMany, true),
+                (1, 2, RelationshipKind::ManyToMany, true),
+                (2, 2, RelationshipKind::ManyToOne, false),
+            ]
+        );
+    }
+}
@@ -0,0 +1,276 @@
+use petgraph::{graph::DiGraph, visit::EdgeRef};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    Diagnostic, Entity, EntityGraph, Microservice, MicroserviceCall, MicroserviceGraph,
+    Relationship, UnresolvedCall,
+};
+
+/// A graph encoded as its nodes and the directed edges between them, where the
+/// `from` and `to` of an edge are indices into `nodes`
+#[derive(Serialize, Deserialize)]
+pub struct GraphRepr<N, E> {
+    pub nodes: Vec<N>,
+    pub edges: Vec<EdgeRepr<E>>,
+}
+
+/// A directed edge between the nodes at two indices of a [`GraphRepr`]
+#[derive(Serialize, Deserialize)]
+pub struct EdgeRepr<E> {
+    pub from: usize,
+    pub to: usize,
+    pub weight: E,
+}
+
+impl<N: Clone, E: Clone> From<&DiGraph<N, E>> for GraphRepr<N, E> {
+    fn from(graph: &DiGraph<N, E>) -> Self {
+        // Node indices are contiguous, so they are the indices into the nodes
+        GraphRepr {
+            nodes: graph.node_weights().cloned().collect(),
+            edges: graph
+                .edge_references()
+                .map(|edge| EdgeRepr {
+                    from: edge.source().index(),
+                    to: edge.target().index(),
+                    weight: edge.weight().clone(),
+                })
+                .collect(),
+        }
+    }
+}
+
+impl<N, E> GraphRepr<N, E> {
+    /// Creates the graph, failing if an edge refers to a node that does not exist
+    fn into_graph(self) -> Result<DiGraph<N, E>, String> {
+        let mut graph = DiGraph::new();
+        let indices: Vec<_> = self
+            .nodes
+            .into_iter()
+            .map(|node| graph.add_node(node))
+            .collect();
+        for edge in self.edges {
+            match (indices.get(edge.from), indices.get(edge.to)) {
+                (Some(from), Some(to)) => {
+                    graph.add_edge(*from, *to, edge.weight);
+                }
+                _ => {
+                    return Err(format!(
+                        "Edge from node {} to node {} refers to a missing node",
+                        edge.from, edge.to
+                    ))
+                }
+            }
+        }
+        Ok(graph)
+    }
+}
+
+/// The encoding of a [`MicroserviceGraph`], which also holds the calls and
+/// objects that could not be added to the graph
+#[derive(Serialize, Deserialize)]
+pub struct MicroserviceGraphRepr {
+    #[serde(flatten)]
+    graph: GraphRepr<Microservice, MicroserviceCall>,
+    #[serde(default)]
+    unresolved_calls: Vec<UnresolvedCall>,
+    #[serde(default)]
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl From<MicroserviceGraph> for MicroserviceGraphRepr {
+    fn from(graph: MicroserviceGraph) -> Self {
+        MicroserviceGraphRepr {
+            graph: GraphRepr::from(&graph.graph),
+            unresolved_calls: graph.unresolved_calls,
+            diagnostics: graph.diagnostics,
+        }
+    }
+}
+
+impl TryFrom<MicroserviceGraphRepr> for MicroserviceGraph {
+    type Error = String;
+
+    fn try_from(repr: MicroserviceGraphRepr) -> Result<Self, Self::Error> {
+        Ok(MicroserviceGraph {
+            graph: repr.graph.into_graph()?,
+            unresolved_calls: repr.unresolved_calls,
+            diagnostics: repr.diagnostics,
+        })
+    }
+}
+
+impl From<EntityGraph> for GraphRepr<Entity, Relationship> {
+    fn from(graph: EntityGraph) -> Self {
+        GraphRepr::from(&graph.0)
+    }
+}
+
+impl TryFrom<GraphRepr<Entity, Relationship>> for EntityGraph {
+    type Error = String;
+
+    fn try_from(repr: GraphRepr<Entity, Relationship>) -> Result<Self, Self::Error> {
+        repr.into_graph().map(EntityGraph)
+    }
+}
+
+/// Encodes an [`http::Method`] as its name, such as `"GET"`
+pub(crate) mod http_method {
+    use std::str::FromStr;
+
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(
+        method: &http::Method,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(method.as_str())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<http::Method, D::Error> {
+        let method = String::deserialize(deserializer)?;
+        http::Method::from_str(&method.to_uppercase()).map_err(D::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::de::DeserializeOwned;
+    use serde_json::{json, Value};
+    use test_case::test_case;
+
+    use super::*;
+    use crate::{Broker, DatabaseType};
+
+    /// Deserializes the JSON and serializes it back, which must give the same JSON
+    fn round_trip<T: Serialize + DeserializeOwned>(value: Value) -> T {
+        let deserialized: T = serde_json::from_value(value.clone()).unwrap();
+        assert_eq!(value, serde_json::to_value(&deserialized).unwrap());
+        deserialized
+    }
+
+    #[test]
+    fn microservice_graph_round_trip() {
+        // The JSON documented on MicroserviceGraph
+        let graph: MicroserviceGraph = round_trip(json!({
+          "nodes": [
+            { "name": "orders", "language": "Java", "ref_entities": [], "endpoints": [] },
+            {
+              "name": "users",
+              "language": "Java",
+              "ref_entities": [],
+              "endpoints": [
+                {
+                  "method": "GET",
+                  "path": "/users/{}",
+                  "handler": "getUser",
+                  "parameters": ["id"],
+                  "return_type": "User"
+                }
+              ]
+            }
+          ],
+          "edges": [
+            {
+              "from": 0,
+              "to": 1,
+              "weight": {
+                "ty": { "http": "GET" },
+                "endpoint": "/users/{}",
+                "calling_method": "UserClient.getUser",
+                "endpoint_function": null,
+                "arguments": ["Long"],
+                "return_type": "User",
+                "location": { "file": "src/UserClient.java", "line": 12 },
+                "target": null
+              }
+            }
+          ],
+          "unresolved_calls": [
+            { "caller": "orders", "callee": "billing", "endpoint": "/bills", "reason": "Unknown microservice" }
+          ],
+          "diagnostics": [
+            {
+              "kind": "missing_key",
+              "object": "field",
+              "parent": "Order",
+              "name": null,
+              "message": "Missing key 'type'"
+            }
+          ]
+        }));
+
+        let edges = graph.edges().into_inner();
+        assert_eq!(1, edges.len());
+        assert_eq!("orders", edges[0].from.name);
+        assert_eq!("users", edges[0].to.name);
+        assert_eq!(1, graph.unresolved_calls().len());
+        assert_eq!(1, graph.diagnostics().len());
+    }
+
+    #[test]
+    fn entity_graph_round_trip() {
+        // The JSON documented on EntityGraph
+        let graph: EntityGraph = round_trip(json!({
+          "nodes": [
+            {
+              "name": "Order",
+              "fields": [
+                {
+                  "name": "items",
+                  "ty": "OrderItem",
+                  "is_collection": true,
+                  "is_id": false,
+                  "nullable": null,
+                  "is_unique": false,
+                  "annotations": ["@OneToMany"]
+                }
+              ],
+              "ty": "MySQL",
+              "sources": [{ "microservice": "orders", "entity": "Order", "ty": "MySQL" }]
+            },
+            { "name": "OrderItem", "fields": [], "ty": "MySQL", "sources": [] }
+          ],
+          "edges": [
+            {
+              "from": 0,
+              "to": 1,
+              "weight": { "source": "one", "target": "many", "field": "items", "inverse_field": null }
+            }
+          ]
+        }));
+
+        let edges = graph.edges().into_inner();
+        assert_eq!(1, edges.len());
+        assert_eq!("Order", edges[0].from.name);
+        assert_eq!("OrderItem", edges[0].to.name);
+    }
+
+    #[test]
+    fn reject_edges_to_missing_nodes() {
+        let graph = json!({
+            "nodes": [{ "name": "Order", "fields": [], "ty": "MySQL" }],
+            "edges": [{ "from": 0, "to": 1, "weight": { "source": "one", "target": "one", "field": "x" } }]
+        });
+        assert!(serde_json::from_value::<EntityGraph>(graph).is_err());
+    }
+
+    #[test_case("MySQL" => "MySQL" ; "mysql")]
+    #[test_case("postgres" => "PostgreSQL" ; "postgres alias")]
+    #[test_case("Mongo" => "MongoDB" ; "mongo alias")]
+    #[test_case("CouchDB" => "CouchDB" ; "unknown")]
+    fn database_type_strings(name: &str) -> String {
+        let ty: DatabaseType = serde_json::from_value(json!(name)).unwrap();
+        round_trip::<DatabaseType>(serde_json::to_value(ty).unwrap()).to_string()
+    }
+
+    #[test_case("Kafka" => "Kafka" ; "kafka")]
+    #[test_case("amqp" => "RabbitMQ" ; "amqp alias")]
+    #[test_case("jms" => "JMS" ; "jms")]
+    #[test_case("NATS" => "NATS" ; "unknown")]
+    fn broker_strings(name: &str) -> String {
+        let broker: Broker = serde_json::from_value(json!(name)).unwrap();
+        round_trip::<Broker>(serde_json::to_value(broker).unwrap()).to_string()
+    }
+}
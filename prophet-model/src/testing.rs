@@ -0,0 +1,36 @@
+//! Builders of microservice graphs for the tests of the prophet crates
+use petgraph::graph::DiGraph;
+use source_code_parser::Language;
+
+use crate::{Microservice, MicroserviceCall, MicroserviceGraph};
+
+/// Creates a microservice without entities or endpoints
+pub fn service(name: &str) -> Microservice {
+    Microservice {
+        name: name.into(),
+        language: Language::Unknown,
+        ref_entities: vec![],
+        endpoints: vec![],
+    }
+}
+
+/// Creates a graph of microservices with the given names and the calls between
+/// the indices of the microservices, without unresolved calls or diagnostics
+pub fn microservice_graph(
+    services: &[&str],
+    calls: impl IntoIterator<Item = (usize, usize, MicroserviceCall)>,
+) -> MicroserviceGraph {
+    let mut graph = DiGraph::new();
+    let nodes: Vec<_> = services
+        .iter()
+        .map(|name| graph.add_node(service(name)))
+        .collect();
+    for (from, to, call) in calls {
+        graph.add_edge(nodes[from], nodes[to], call);
+    }
+    MicroserviceGraph {
+        graph,
+        unresolved_calls: vec![],
+        diagnostics: vec![],
+    }
+}
@@ -0,0 +1,265 @@
+use crate::normalize;
+
+/// Containers of many values, whose element type is their first type argument
+const COLLECTIONS: &[&str] = &[
+    "list",
+    "arraylist",
+    "linkedlist",
+    "collection",
+    "iterable",
+    "set",
+    "hashset",
+    "linkedhashset",
+    "treeset",
+    "sortedset",
+    "frozenset",
+    "vector",
+    "vec",
+    "deque",
+    "arraydeque",
+    "queue",
+    "array",
+    "readonlyarray",
+    "sequence",
+    "seq",
+    "stream",
+    "flux",
+];
+
+/// Maps of keys to values, whose element type is their last type argument
+const MAPS: &[&str] = &[
+    "map",
+    "hashmap",
+    "linkedhashmap",
+    "treemap",
+    "sortedmap",
+    "unorderedmap",
+    "multimap",
+    "dict",
+    "dictionary",
+    "mapping",
+    "record",
+];
+
+/// Wrappers of at most one value, whose element type is their first type argument
+const WRAPPERS: &[&str] = &[
+    "optional",
+    "option",
+    "nullable",
+    "sharedptr",
+    "uniqueptr",
+    "weakptr",
+    "box",
+    "rc",
+    "arc",
+    "ref",
+    "lazy",
+    "mono",
+    "future",
+    "completablefuture",
+    "promise",
+];
+
+/// The types that make up the null side of a union like `Order | None`
+const NULLS: &[&str] = &["null", "none", "undefined", "nil"];
+
+/// A field type reduced to the type it refers to, such as `Order` for
+/// `Optional<List<com.shop.Order>>`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TypeExpr {
+    /// The unqualified name of the referred type
+    pub name: String,
+    /// Whether the field holds many values of the type
+    pub is_collection: bool,
+    /// Whether the field may hold no value of the type
+    pub is_optional: bool,
+}
+
+impl TypeExpr {
+    fn named(name: &str) -> Self {
+        TypeExpr {
+            name: unqualified(name).to_string(),
+            is_collection: false,
+            is_optional: false,
+        }
+    }
+
+    /// Parses a type of a Java, C++, Go, Python or TypeScript field, unwrapping
+    /// any containers, generics, pointers and unions with null
+    pub fn parse(ty: &str) -> Self {
+        let ty = ty.trim();
+
+        // Unions refer to their first type that is not null, and are only optional
+        // if one of their types is, such as `Order | None` or `Order | undefined`
+        let parts = split_top_level(ty, '|');
+        if parts.len() > 1 {
+            let (nulls, types): (Vec<&str>, Vec<&str>) = parts
+                .into_iter()
+                .partition(|part| NULLS.contains(&part.to_lowercase().as_str()));
+            let ty = TypeExpr::parse(types.first().copied().unwrap_or_default());
+            return TypeExpr {
+                is_optional: ty.is_optional || !nulls.is_empty(),
+                ..ty
+            };
+        }
+
+        // Qualifiers, pointers, references and nullable markers
+        let ty = ty.strip_prefix("const ").unwrap_or(ty);
+        let ty = ty.strip_prefix("struct ").unwrap_or(ty);
+        let ty = ty
+            .trim_start_matches(['*', '&'])
+            .trim_end_matches(|c: char| c == '*' || c == '&' || c.is_whitespace());
+        if let Some(ty) = ty.strip_suffix('?') {
+            return TypeExpr {
+                is_optional: true,
+                ..TypeExpr::parse(ty)
+            };
+        }
+
+        // Arrays and slices, such as `Order[]`, `[]Order` or `[4]Order`
+        if let Some(element) = ty.strip_suffix("[]") {
+            return TypeExpr::parse(element).into_collection();
+        }
+        if let Some(rest) = ty.strip_prefix('[') {
+            if let Some((size, element)) = rest.split_once(']') {
+                if size.chars().all(|c| c.is_ascii_digit()) {
+                    return TypeExpr::parse(element).into_collection();
+                }
+            }
+        }
+
+        // Go maps, such as `map[string]Order`
+        if let Some(rest) = ty.strip_prefix("map[") {
+            if let Some(close) = matching_close(rest, '[', ']') {
+                return TypeExpr::parse(&rest[close + 1..]).into_collection();
+            }
+        }
+
+        // Generics, such as `List<Order>` or `Dict[str, Order]`
+        let open = match ty.find(['<', '[']) {
+            Some(open) => open,
+            None => return TypeExpr::named(ty),
+        };
+        let (opening, closing) = if ty[open..].starts_with('<') {
+            ('<', '>')
+        } else {
+            ('[', ']')
+        };
+        let close = match matching_close(&ty[open + 1..], opening, closing) {
+            Some(close) => open + 1 + close,
+            None => return TypeExpr::named(ty),
+        };
+        let base = unqualified(&ty[..open]);
+        let args = split_top_level(&ty[open + 1..close], ',');
+
+        // Fixed size C-style arrays, such as `Order[4]`
+        if opening == '['
+            && args
+                .iter()
+                .all(|arg| arg.chars().all(|c| c.is_ascii_digit()))
+        {
+            return TypeExpr::parse(base).into_collection();
+        }
+
+        let kind = normalize(base);
+        match (args.first(), args.last()) {
+            (Some(element), _) if COLLECTIONS.contains(&kind.as_str()) => {
+                TypeExpr::parse(element).into_collection()
+            }
+            (_, Some(value)) if MAPS.contains(&kind.as_str()) => {
+                TypeExpr::parse(value).into_collection()
+            }
+            (Some(element), _) if WRAPPERS.contains(&kind.as_str()) => TypeExpr {
+                is_optional: true,
+                ..TypeExpr::parse(element)
+            },
+            // Any other generic type is the type it refers to
+            _ => TypeExpr::named(base),
+        }
+    }
+
+    fn into_collection(self) -> Self {
+        TypeExpr {
+            is_collection: true,
+            ..self
+        }
+    }
+}
+
+/// Strips any package, module or namespace qualifiers from a type name
+fn unqualified(name: &str) -> &str {
+    let name = name.trim();
+    let start = name.rfind(['.', ':']).map_or(0, |ndx| ndx + 1);
+    &name[start..]
+}
+
+/// Finds the index of the bracket closing an already opened bracket
+fn matching_close(s: &str, opening: char, closing: char) -> Option<usize> {
+    let mut depth = 0;
+    for (ndx, c) in s.char_indices() {
+        if c == opening {
+            depth += 1;
+        } else if c == closing {
+            if depth == 0 {
+                return Some(ndx);
+            }
+            depth -= 1;
+        }
+    }
+    None
+}
+
+/// Splits a type on a separator, except inside brackets
+pub(crate) fn split_top_level(s: &str, separator: char) -> Vec<&str> {
+    let mut parts = vec![];
+    let mut depth = 0;
+    let mut start = 0;
+    let mut previous = None;
+    for (ndx, c) in s.char_indices() {
+        match c {
+            '<' | '[' | '(' => depth += 1,
+            // Arrows like `=>` and `->` do not close a bracket
+            '>' if matches!(previous, Some('=' | '-')) => (),
+            '>' | ']' | ')' => depth -= 1,
+            c if c == separator && depth == 0 => {
+                parts.push(s[start..ndx].trim());
+                start = ndx + c.len_utf8();
+            }
+            _ => (),
+        }
+        previous = Some(c);
+    }
+    parts.push(s[start..].trim());
+    parts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    #[test_case("Order" => ("Order", false, false) ; "plain")]
+    #[test_case("com.shop.model.Order" => ("Order", false, false) ; "java_qualified")]
+    #[test_case("List<com.foo.Order>" => ("Order", true, false) ; "java_list")]
+    #[test_case("Optional<Order>" => ("Order", false, true) ; "java_optional")]
+    #[test_case("Set<Item>" => ("Item", true, false) ; "java_set")]
+    #[test_case("Map<String, Address>" => ("Address", true, false) ; "java_map")]
+    #[test_case("Map<String, List<Address>>" => ("Address", true, false) ; "nested_map")]
+    #[test_case("Order[]" => ("Order", true, false) ; "array")]
+    #[test_case("std::vector<Post>" => ("Post", true, false) ; "cpp_vector")]
+    #[test_case("const std::shared_ptr<Post>&" => ("Post", false, true) ; "cpp_shared_ptr")]
+    #[test_case("Post*" => ("Post", false, false) ; "cpp_pointer")]
+    #[test_case("[]*models.User" => ("User", true, false) ; "go_slice")]
+    #[test_case("map[string]User" => ("User", true, false) ; "go_map")]
+    #[test_case("Optional[List[Order]]" => ("Order", true, true) ; "python_optional_list")]
+    #[test_case("Dict[str, Order]" => ("Order", true, false) ; "python_dict")]
+    #[test_case("Order | None" => ("Order", false, true) ; "python_union")]
+    #[test_case("int | str" => ("int", false, false) ; "python_union_without_none")]
+    #[test_case("Array<Order>" => ("Order", true, false) ; "ts_array")]
+    #[test_case("Record<string, Order> | undefined" => ("Order", true, true) ; "ts_record")]
+    #[test_case("Page<Order>" => ("Page", false, false) ; "other_generic")]
+    fn parse_type(ty: &str) -> (String, bool, bool) {
+        let ty = TypeExpr::parse(ty);
+        (ty.name, ty.is_collection, ty.is_optional)
+    }
+}
@@ -0,0 +1,54 @@
+use actix_web::{http::StatusCode, HttpResponse, ResponseError};
+use prophet::BoundedContextError;
+use serde::Serialize;
+
+/// A failed analysis, responded to with a JSON body describing the failure
+#[derive(Debug)]
+pub struct AnalysisError(pub prophet::Error);
+
+/// The JSON body of an error response. A failed call to the bounded context
+/// service does not fail the analysis, it is reported as a warning instead
+#[derive(Serialize)]
+struct ErrorBody {
+    error: &'static str,
+    message: String,
+}
+
+impl AnalysisError {
+    fn kind(&self) -> &'static str {
+        use prophet::Error::*;
+        match &self.0 {
+            CloneRepo(_) => "clone_repo",
+            InvalidRepository(_) => "invalid_repository",
+            Io(_) => "io",
+            AppData(_) => "app_data",
+            BoundedContext(_) => "bounded_context",
+            Cancelled => "cancelled",
+        }
+    }
+}
+
+impl std::fmt::Display for AnalysisError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl ResponseError for AnalysisError {
+    fn status_code(&self) -> StatusCode {
+        match &self.0 {
+            prophet::Error::InvalidRepository(_)
+            | prophet::Error::BoundedContext(BoundedContextError::InvalidOptions(_)) => {
+                StatusCode::BAD_REQUEST
+            }
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        HttpResponse::build(self.status_code()).json(ErrorBody {
+            error: self.kind(),
+            message: self.0.to_string(),
+        })
+    }
+}
@@ -0,0 +1,382 @@
+use std::{
+    any::Any,
+    collections::HashMap,
+    panic::AssertUnwindSafe,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex, MutexGuard,
+    },
+    time::{Duration, Instant},
+};
+
+use actix_web::{error::BlockingError, web};
+use futures::{
+    future::{abortable, AbortHandle},
+    FutureExt,
+};
+use prophet::{AnalysisOptions, AppData, CancellationToken, Stage};
+use serde::Serialize;
+
+use crate::routes::AnalysisBody;
+
+/// The identifier of an analysis job
+pub type JobId = u64;
+
+/// Whether an analysis job is still running or how it finished
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobState {
+    Running,
+    Completed,
+    Failed,
+}
+
+/// The reported status of an analysis job
+#[derive(Debug, Clone, Serialize)]
+pub struct JobStatus {
+    pub id: JobId,
+    pub state: JobState,
+    /// The last stage of the analysis that was started, if any
+    pub stage: Option<Stage>,
+    /// The reason the analysis failed, if it did
+    pub error: Option<String>,
+}
+
+/// An analysis job running in the background
+struct Job {
+    status: JobStatus,
+    result: Option<AppData>,
+    abort: AbortHandle,
+    cancel: CancellationToken,
+    /// When the job completed or failed
+    finished_at: Option<Instant>,
+}
+
+/// The registry of all analysis jobs known to the service
+pub struct Jobs {
+    next_id: AtomicU64,
+    jobs: Mutex<HashMap<JobId, Job>>,
+    /// How long to keep a finished job and its result around for
+    ttl: Duration,
+    /// How many finished jobs to keep at most, forgetting the oldest first
+    max_finished: usize,
+}
+
+impl Jobs {
+    pub fn new(ttl: Duration, max_finished: usize) -> Jobs {
+        Jobs {
+            next_id: AtomicU64::default(),
+            jobs: Mutex::default(),
+            ttl,
+            max_finished,
+        }
+    }
+
+    /// Starts analyzing the provided body in the background, returning the
+    /// id of the job to poll its status with
+    pub fn spawn(jobs: web::Data<Jobs>, options: AnalysisOptions, body: AnalysisBody) -> JobId {
+        let id = jobs.next_id.fetch_add(1, Ordering::Relaxed);
+        let registry = jobs.clone();
+        let cancel = CancellationToken::default();
+
+        let task_cancel = cancel.clone();
+        let (task, abort) = abortable(async move {
+            let analysis = async {
+                // Cloning, parsing and running the ReSSAs block, so they run on the thread
+                // pool instead of the worker, which keeps serving requests meanwhile
+                let blocking_registry = registry.clone();
+                let blocking_options = options.clone();
+                let analysis = web::block(move || {
+                    AppData::analyze_repositories(
+                        body.repositories,
+                        body.ressa_dir,
+                        &blocking_options,
+                        |stage| blocking_registry.set_stage(id, stage),
+                        &task_cancel,
+                    )
+                })
+                .await
+                .map_err(|err| match err {
+                    BlockingError::Error(err) => err,
+                    BlockingError::Canceled => {
+                        prophet::Error::AppData("The analysis thread stopped unexpectedly".into())
+                    }
+                });
+
+                match analysis {
+                    Ok(analysis) => {
+                        AppData::from_repository_analysis(analysis, &options, |stage| {
+                            registry.set_stage(id, stage)
+                        })
+                        .await
+                    }
+                    Err(err) => Err(err),
+                }
+            };
+
+            // A panicking analysis fails its job instead of leaving it running forever
+            let result = AssertUnwindSafe(analysis)
+                .catch_unwind()
+                .await
+                .unwrap_or_else(|panic| {
+                    Err(prophet::Error::AppData(format!(
+                        "The analysis panicked: {}",
+                        panic_message(&*panic)
+                    )))
+                });
+            registry.finish(id, result);
+        });
+
+        jobs.lock().insert(
+            id,
+            Job {
+                status: JobStatus {
+                    id,
+                    state: JobState::Running,
+                    stage: None,
+                    error: None,
+                },
+                result: None,
+                abort,
+                cancel,
+                finished_at: None,
+            },
+        );
+
+        // A cancelled job stops at its next stage and drops its workspace, which
+        // cleans up the cloned repositories
+        actix_web::rt::spawn(async move {
+            let _ = task.await;
+        });
+        id
+    }
+
+    /// Gets the status of a job
+    pub fn status(&self, id: JobId) -> Option<JobStatus> {
+        let jobs = self.lock();
+        jobs.get(&id).map(|job| job.status.clone())
+    }
+
+    /// Calls `f` with the status and result of a job, if the job exists
+    pub fn with_result<T>(
+        &self,
+        id: JobId,
+        f: impl FnOnce(&JobStatus, Option<&AppData>) -> T,
+    ) -> Option<T> {
+        let jobs = self.lock();
+        jobs.get(&id).map(|job| f(&job.status, job.result.as_ref()))
+    }
+
+    /// Cancels a job if it is still running and forgets about it, returning
+    /// whether the job existed
+    pub fn cancel(&self, id: JobId) -> bool {
+        match self.lock().remove(&id) {
+            Some(job) => {
+                job.cancel.cancel();
+                job.abort.abort();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Locks the jobs, forgetting the finished jobs that expired first
+    fn lock(&self) -> MutexGuard<'_, HashMap<JobId, Job>> {
+        let mut jobs = self.jobs.lock().unwrap();
+        evict(&mut jobs, Instant::now(), self.ttl, self.max_finished);
+        jobs
+    }
+
+    fn set_stage(&self, id: JobId, stage: Stage) {
+        if let Some(job) = self.lock().get_mut(&id) {
+            job.status.stage = Some(stage);
+        }
+    }
+
+    fn finish(&self, id: JobId, result: Result<AppData, prophet::Error>) {
+        // The job may have been cancelled in the meantime
+        let mut jobs = self.lock();
+        let job = match jobs.get_mut(&id) {
+            Some(job) => job,
+            None => return,
+        };
+
+        job.finished_at = Some(Instant::now());
+        match result {
+            Ok(app_data) => {
+                job.status.state = JobState::Completed;
+                job.result = Some(app_data);
+            }
+            Err(err) => {
+                tracing::warn!("Analysis job {} failed: {}", id, err);
+                job.status.state = JobState::Failed;
+                job.status.error = Some(err.to_string());
+            }
+        }
+    }
+}
+
+/// Forgets the finished jobs older than the TTL, and then the oldest finished
+/// jobs beyond the maximum. Running jobs are never forgotten
+fn evict(jobs: &mut HashMap<JobId, Job>, now: Instant, ttl: Duration, max_finished: usize) {
+    jobs.retain(|_, job| match job.finished_at {
+        Some(finished_at) => now.saturating_duration_since(finished_at) < ttl,
+        None => true,
+    });
+
+    let mut finished: Vec<_> = jobs
+        .iter()
+        .filter_map(|(id, job)| job.finished_at.map(|finished_at| (finished_at, *id)))
+        .collect();
+    if finished.len() > max_finished {
+        finished.sort();
+        for (_, id) in finished.iter().take(finished.len() - max_finished) {
+            jobs.remove(id);
+        }
+    }
+}
+
+/// Gets the message a panic was started with, if it has one
+fn panic_message(panic: &(dyn Any + Send)) -> &str {
+    match panic.downcast_ref::<&str>() {
+        Some(message) => message,
+        None => panic
+            .downcast_ref::<String>()
+            .map_or("unknown panic", String::as_str),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{http::StatusCode, test, App};
+
+    use super::*;
+    use crate::routes;
+
+    fn job(id: JobId, finished_at: Option<Instant>) -> (JobId, Job) {
+        let (abort, _) = AbortHandle::new_pair();
+        let job = Job {
+            status: JobStatus {
+                id,
+                state: JobState::Running,
+                stage: None,
+                error: None,
+            },
+            result: None,
+            abort,
+            cancel: CancellationToken::default(),
+            finished_at,
+        };
+        (id, job)
+    }
+
+    #[test]
+    fn evict_finished_jobs() {
+        let now = Instant::now();
+        let ago = |secs| Some(now - Duration::from_secs(secs));
+        let mut jobs: HashMap<_, _> = vec![
+            job(0, None),
+            job(1, ago(120)),
+            job(2, ago(30)),
+            job(3, ago(20)),
+            job(4, ago(10)),
+        ]
+        .into_iter()
+        .collect();
+
+        evict(&mut jobs, now, Duration::from_secs(60), 2);
+        let mut ids: Vec<_> = jobs.keys().copied().collect();
+        ids.sort_unstable();
+        assert_eq!(ids, vec![0, 3, 4]);
+    }
+
+    /// Registers a running job, as [`Jobs::spawn`] does before running it
+    fn register(jobs: &Jobs, id: JobId, abort: AbortHandle) -> CancellationToken {
+        let (id, job) = job(id, None);
+        let cancel = job.cancel.clone();
+        jobs.lock().insert(id, Job { abort, ..job });
+        cancel
+    }
+
+    fn state(jobs: &Jobs, id: JobId) -> Option<(JobState, Option<Stage>)> {
+        jobs.status(id).map(|status| (status.state, status.stage))
+    }
+
+    #[test]
+    fn report_job_states() {
+        let jobs = Jobs::new(Duration::from_secs(60), 10);
+        for id in [0, 1] {
+            register(&jobs, id, AbortHandle::new_pair().0);
+        }
+        assert_eq!(Some((JobState::Running, None)), state(&jobs, 0));
+
+        jobs.set_stage(0, Stage::Cloning);
+        assert_eq!(
+            Some((JobState::Running, Some(Stage::Cloning))),
+            state(&jobs, 0)
+        );
+        jobs.set_stage(0, Stage::Rendering);
+        jobs.finish(0, Ok(AppData::default()));
+        assert_eq!(
+            Some((JobState::Completed, Some(Stage::Rendering))),
+            state(&jobs, 0)
+        );
+        assert_eq!(
+            Some(true),
+            jobs.with_result(0, |_, result| result.is_some())
+        );
+
+        jobs.set_stage(1, Stage::Parsing);
+        jobs.finish(1, Err(prophet::Error::AppData("No ReSSA result".into())));
+        let status = jobs.status(1).unwrap();
+        assert_eq!(
+            (JobState::Failed, Some(Stage::Parsing)),
+            (status.state, status.stage)
+        );
+        assert!(status.error.unwrap().contains("No ReSSA result"));
+        assert_eq!(
+            Some(false),
+            jobs.with_result(1, |_, result| result.is_some())
+        );
+    }
+
+    #[test]
+    fn cancel_running_jobs() {
+        actix_web::rt::System::new("test").block_on(async {
+            let jobs = web::Data::new(Jobs::new(Duration::from_secs(60), 10));
+            let (task, abort) = abortable(futures::future::pending::<()>());
+            let cancel = register(&jobs, 0, abort);
+            let mut app = test::init_service(
+                App::new()
+                    .app_data(jobs.clone())
+                    .service(routes::job_status)
+                    .service(routes::cancel_job),
+            )
+            .await;
+            let get = || test::TestRequest::get().uri("/jobs/0").to_request();
+            let delete = || test::TestRequest::delete().uri("/jobs/0").to_request();
+
+            assert_eq!(
+                StatusCode::OK,
+                test::call_service(&mut app, get()).await.status()
+            );
+            assert_eq!(
+                StatusCode::NO_CONTENT,
+                test::call_service(&mut app, delete()).await.status()
+            );
+            assert!(task.await.is_err());
+            assert!(cancel.is_cancelled());
+
+            // A cancelled job is forgotten, even if its analysis finishes afterwards
+            jobs.finish(0, Ok(AppData::default()));
+            assert_eq!(
+                StatusCode::NOT_FOUND,
+                test::call_service(&mut app, get()).await.status()
+            );
+            assert_eq!(
+                StatusCode::NOT_FOUND,
+                test::call_service(&mut app, delete()).await.status()
+            );
+        });
+    }
+}
@@ -1,7 +1,17 @@
+use std::{path::PathBuf, time::Duration};
+
 use actix_web::{middleware::Logger, web, App, FromRequest, HttpServer};
-use prophet::Repositories;
+use prophet::{
+    AnalysisOptions, BoundedContextClient, BoundedContextOptions, Credentials, MergeStrategy,
+    Repositories,
+};
 use structopt::StructOpt;
 
+mod error;
+
+mod jobs;
+use jobs::Jobs;
+
 mod routes;
 use routes::*;
 
@@ -11,6 +21,27 @@ struct Opt {
     host: String,
     #[structopt(long, short, default_value = "8080")]
     port: i32,
+    /// The directory to clone the repositories of each analysis under
+    #[structopt(long, env = "PROPHET_WORKSPACE_ROOT", parse(from_os_str))]
+    workspace_root: Option<PathBuf>,
+    /// How to merge the entities of all microservices, either "remote" or "native",
+    /// when a request does not provide its own bounded context options
+    #[structopt(long, env = "PROPHET_MERGE_STRATEGY", default_value = "remote")]
+    merge_strategy: MergeStrategy,
+    /// A JSON file configuring the client for the bounded context service. The
+    /// PROPHET_BC_* environment variables are used when it is not provided
+    #[structopt(long, env = "PROPHET_BC_CONFIG", parse(from_os_str))]
+    bounded_context_config: Option<PathBuf>,
+    /// The directory that repositories analyzed in place by /analyze/local must be
+    /// checked out under. The endpoint is disabled when it is not provided
+    #[structopt(long, env = "PROPHET_LOCAL_ROOT", parse(from_os_str))]
+    local_root: Option<PathBuf>,
+    /// How many seconds to keep the status and result of a finished job for
+    #[structopt(long, env = "PROPHET_JOB_TTL_SECS", default_value = "3600")]
+    job_ttl_secs: u64,
+    /// How many finished jobs to keep at most, forgetting the oldest first
+    #[structopt(long, env = "PROPHET_MAX_FINISHED_JOBS", default_value = "100")]
+    max_finished_jobs: usize,
 }
 
 #[actix_web::main]
@@ -19,9 +50,41 @@ async fn main() -> std::io::Result<()> {
     let opt = Opt::from_args();
     let addr = format!("{}:{}", opt.host, opt.port);
 
-    HttpServer::new(|| {
+    let jobs = web::Data::new(Jobs::new(
+        Duration::from_secs(opt.job_ttl_secs),
+        opt.max_finished_jobs,
+    ));
+    let bounded_context_client = match &opt.bounded_context_config {
+        Some(path) => BoundedContextClient::from_file(path),
+        None => BoundedContextClient::from_env(),
+    }
+    .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidInput, err))?;
+
+    let defaults = AnalysisOptions::default();
+    let options = web::Data::new(AnalysisOptions {
+        credentials: Credentials::from_env(),
+        workspace_root: opt.workspace_root.unwrap_or(defaults.workspace_root),
+        bounded_context: BoundedContextOptions {
+            strategy: opt.merge_strategy,
+            ..defaults.bounded_context
+        },
+        bounded_context_client,
+        local_root: opt.local_root,
+        ..defaults
+    });
+
+    HttpServer::new(move || {
         App::new()
             .service(analyze)
+            .service(analyze_v1)
+            .service(analyze_local)
+            .service(create_job)
+            .service(job_status)
+            .service(job_result)
+            .service(job_metrics)
+            .service(cancel_job)
+            .app_data(jobs.clone())
+            .app_data(options.clone())
             .wrap(Logger::default())
             .app_data(web::Json::<Repositories>::configure(|cfg| {
                 cfg.limit(1024 * 1024 * 4)
@@ -1,18 +1,192 @@
-use actix_web::{error, post, web, Error, HttpResponse};
-use prophet::{AppData, Repositories};
+use actix_web::{delete, error, get, post, web, Error, HttpResponse};
+use prophet::{
+    adapter, AnalysisOptions, AppData, BoundedContextOverrides, EntityDiagramStyle,
+    LocalRepositories, Repositories,
+};
 use serde::Deserialize;
 
+use crate::error::AnalysisError;
+use crate::jobs::{JobId, JobState, Jobs};
+
 #[derive(Deserialize)]
 pub struct AnalysisBody {
-    ressa_dir: String,
-    repositories: Repositories,
+    pub ressa_dir: String,
+    pub repositories: Repositories,
+    /// How to merge the entities of the analyzed microservices, overriding only
+    /// the provided fields of the service's configured options
+    #[serde(default)]
+    pub bounded_context: Option<BoundedContextOverrides>,
+    /// Whether to include the analyzed graphs in the response besides their diagrams
+    #[serde(default)]
+    pub include_graphs: bool,
+    /// How to render the entity diagrams, instead of the service's default
+    #[serde(default)]
+    pub entity_diagram: Option<EntityDiagramStyle>,
+}
+
+/// Overrides the service's default options with those provided in a request
+fn request_options(
+    options: &AnalysisOptions,
+    bounded_context: Option<BoundedContextOverrides>,
+    include_graphs: bool,
+    entity_diagram: Option<EntityDiagramStyle>,
+) -> AnalysisOptions {
+    let mut options = options.clone();
+    if let Some(bounded_context) = bounded_context {
+        options.bounded_context = options.bounded_context.with_overrides(bounded_context);
+    }
+    options.include_graphs |= include_graphs;
+    if let Some(entity_diagram) = entity_diagram {
+        options.entity_diagram = entity_diagram;
+    }
+    options
 }
 
 #[post("/analyze")]
-pub async fn analyze(payload: web::Json<AnalysisBody>) -> Result<HttpResponse, Error> {
+pub async fn analyze(
+    options: web::Data<AnalysisOptions>,
+    payload: web::Json<AnalysisBody>,
+) -> Result<HttpResponse, Error> {
     let payload = payload.into_inner();
-    let app_data = AppData::from_repositories(payload.repositories, payload.ressa_dir)
+    let options = request_options(
+        &options,
+        payload.bounded_context,
+        payload.include_graphs,
+        payload.entity_diagram,
+    );
+    let app_data = AppData::from_repositories(payload.repositories, payload.ressa_dir, &options)
         .await
-        .map_err(error::ErrorInternalServerError)?;
+        .map_err(AnalysisError)?;
+    Ok(HttpResponse::Ok().json(app_data))
+}
+
+/// Analyzes the repositories like `/analyze`, responding in the format of the
+/// Prophet v1 frontend
+#[post("/v1/analyze")]
+pub async fn analyze_v1(
+    options: web::Data<AnalysisOptions>,
+    payload: web::Json<AnalysisBody>,
+) -> Result<HttpResponse, Error> {
+    let payload = payload.into_inner();
+    let options = request_options(
+        &options,
+        payload.bounded_context,
+        payload.include_graphs,
+        payload.entity_diagram,
+    );
+    let app_data =
+        adapter::AppData::from_repositories(payload.repositories, payload.ressa_dir, &options)
+            .await
+            .map_err(AnalysisError)?;
     Ok(HttpResponse::Ok().json(app_data))
 }
+
+#[derive(Deserialize)]
+pub struct LocalAnalysisBody {
+    ressa_dir: String,
+    repositories: LocalRepositories,
+    #[serde(default)]
+    bounded_context: Option<BoundedContextOverrides>,
+    #[serde(default)]
+    include_graphs: bool,
+    #[serde(default)]
+    entity_diagram: Option<EntityDiagramStyle>,
+}
+
+/// Analyzes repositories checked out under the configured local root, which
+/// must be set for the endpoint to be enabled
+#[post("/analyze/local")]
+pub async fn analyze_local(
+    options: web::Data<AnalysisOptions>,
+    payload: web::Json<LocalAnalysisBody>,
+) -> Result<HttpResponse, Error> {
+    if options.local_root.is_none() {
+        return Err(error::ErrorForbidden(
+            "Analyzing local repositories is disabled, since no local root is configured",
+        ));
+    }
+    let payload = payload.into_inner();
+    let options = request_options(
+        &options,
+        payload.bounded_context,
+        payload.include_graphs,
+        payload.entity_diagram,
+    );
+    let app_data = AppData::from_paths(payload.repositories, payload.ressa_dir, &options)
+        .await
+        .map_err(AnalysisError)?;
+    Ok(HttpResponse::Ok().json(app_data))
+}
+
+#[post("/jobs")]
+pub async fn create_job(
+    jobs: web::Data<Jobs>,
+    options: web::Data<AnalysisOptions>,
+    payload: web::Json<AnalysisBody>,
+) -> Result<HttpResponse, Error> {
+    let mut payload = payload.into_inner();
+    let options = request_options(
+        &options,
+        payload.bounded_context.take(),
+        payload.include_graphs,
+        payload.entity_diagram,
+    );
+    let id = Jobs::spawn(jobs.clone(), options, payload);
+    let status = jobs
+        .status(id)
+        .ok_or_else(|| error::ErrorInternalServerError("Job was not registered"))?;
+    Ok(HttpResponse::Accepted().json(status))
+}
+
+#[get("/jobs/{id}")]
+pub async fn job_status(
+    jobs: web::Data<Jobs>,
+    id: web::Path<JobId>,
+) -> Result<HttpResponse, Error> {
+    let status = jobs
+        .status(id.into_inner())
+        .ok_or_else(|| error::ErrorNotFound("No such job"))?;
+    Ok(HttpResponse::Ok().json(status))
+}
+
+#[get("/jobs/{id}/result")]
+pub async fn job_result(
+    jobs: web::Data<Jobs>,
+    id: web::Path<JobId>,
+) -> Result<HttpResponse, Error> {
+    jobs.with_result(id.into_inner(), |status, app_data| match app_data {
+        Some(app_data) => HttpResponse::Ok().json(app_data),
+        None if status.state == JobState::Failed => {
+            HttpResponse::InternalServerError().json(status)
+        }
+        None => HttpResponse::Accepted().json(status),
+    })
+    .ok_or_else(|| error::ErrorNotFound("No such job"))
+}
+
+#[get("/jobs/{id}/metrics")]
+pub async fn job_metrics(
+    jobs: web::Data<Jobs>,
+    id: web::Path<JobId>,
+) -> Result<HttpResponse, Error> {
+    jobs.with_result(id.into_inner(), |status, app_data| match app_data {
+        Some(app_data) => HttpResponse::Ok().json(&app_data.metrics),
+        None if status.state == JobState::Failed => {
+            HttpResponse::InternalServerError().json(status)
+        }
+        None => HttpResponse::Accepted().json(status),
+    })
+    .ok_or_else(|| error::ErrorNotFound("No such job"))
+}
+
+#[delete("/jobs/{id}")]
+pub async fn cancel_job(
+    jobs: web::Data<Jobs>,
+    id: web::Path<JobId>,
+) -> Result<HttpResponse, Error> {
+    if jobs.cancel(id.into_inner()) {
+        Ok(HttpResponse::NoContent().finish())
+    } else {
+        Err(error::ErrorNotFound("No such job"))
+    }
+}
@@ -1,31 +1,149 @@
+//! Compatibility types for the Prophet v1 frontend
+
 use std::path::Path;
 
 use super::Error;
 use serde::Serialize;
 
-use super::Repositories;
+use super::{AnalysisOptions, Repositories};
 
 /// A compatibility AppData type for the current Prophet frontend
+///
+/// The serialized representation in JSON is as follows, where missing diagrams
+/// are empty strings
+/// ```json
+/// {
+///   "global": {
+///     "projectName": "system",
+///     "communication": "graph TD\n...",
+///     "context": "classDiagram\n..."
+///   },
+///   "ms": [
+///     {
+///       "name": "order-service",
+///       "boundedContext": "classDiagram\n..."
+///     }
+///   ]
+/// }
+/// ```
 #[derive(Debug, Default, Serialize)]
-pub struct AppData {}
+pub struct AppData {
+    /// The diagrams of the whole project
+    pub global: Global,
+    /// The analyzed microservices
+    pub ms: Vec<Microservice>,
+}
+
+/// The diagrams of the whole project in the v1 format
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Global {
+    /// The application or project name
+    pub project_name: String,
+    /// The mermaid communication diagram
+    pub communication: String,
+    /// The mermaid diagram of the bounded context
+    pub context: String,
+}
+
+/// An analyzed microservice in the v1 format
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Microservice {
+    /// The name of the microservice
+    pub name: String,
+    /// The mermaid diagram of the entities of the microservice
+    pub bounded_context: String,
+}
 
 impl AppData {
-    #[allow(dead_code)]
     /// Clone the provided repositories and generate ReSSAs to analyze them
     /// based on the languages in its LAAST
     pub async fn from_repositories<P: AsRef<Path>>(
         repos: Repositories,
         ressa_dir: P,
+        options: &AnalysisOptions,
     ) -> Result<AppData, Error> {
-        super::AppData::from_repositories(repos, ressa_dir)
+        super::AppData::from_repositories(repos, ressa_dir, options)
             .await
             .map(AppData::from)
     }
 }
 
 impl From<super::AppData> for AppData {
-    fn from(_app_data: super::AppData) -> Self {
-        // TODO
-        AppData::default()
+    fn from(app_data: super::AppData) -> Self {
+        let ms = app_data
+            .microservices
+            .into_iter()
+            .map(|ms| Microservice {
+                name: ms.name,
+                bounded_context: ms.entity_diagram.map(String::from).unwrap_or_default(),
+            })
+            .collect();
+
+        AppData {
+            global: Global {
+                project_name: app_data.name,
+                communication: app_data
+                    .communication_diagram
+                    .map(String::from)
+                    .unwrap_or_default(),
+                context: app_data
+                    .entity_diagram
+                    .map(String::from)
+                    .unwrap_or_default(),
+            },
+            ms,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use prophet_mermaid::MermaidString;
+    use prophet_model::{DatabaseType, Entity, EntityGraph};
+
+    use super::*;
+
+    fn diagram(entity: &str) -> MermaidString {
+        let entities = [Entity::new(entity, vec![], DatabaseType::MySQL)];
+        MermaidString::from(EntityGraph::try_new(&entities).unwrap())
+    }
+
+    #[test]
+    fn serialize_v1_format() {
+        let app_data = crate::AppData {
+            name: "system".into(),
+            communication_diagram: Some(diagram("Order")),
+            entity_diagram: None,
+            microservices: vec![
+                crate::Microservice {
+                    name: "orders".into(),
+                    entity_diagram: Some(diagram("Order")),
+                    ..Default::default()
+                },
+                crate::Microservice {
+                    name: "users".into(),
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+
+        let order = String::from(diagram("Order"));
+        assert_eq!(
+            serde_json::json!({
+                "global": {
+                    "projectName": "system",
+                    "communication": order,
+                    "context": "",
+                },
+                "ms": [
+                    { "name": "orders", "boundedContext": order },
+                    { "name": "users", "boundedContext": "" },
+                ],
+            }),
+            serde_json::to_value(AppData::from(app_data)).unwrap()
+        );
     }
 }
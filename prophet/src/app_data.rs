@@ -1,11 +1,20 @@
-use std::path::Path;
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
 
-use crate::{Error, Repositories};
+use crate::{
+    BoundedContextClient, BoundedContextOptions, CancellationToken, Credentials, Error,
+    LocalRepositories, Repositories, RepositoryVersion, Stage, Warning, Workspace,
+};
 use prophet_ressa::run_ressa;
 
-use prophet_bounded_context::get_bounded_context;
-use prophet_mermaid::MermaidString;
-use prophet_model::MicroserviceGraph;
+use prophet_bounded_context::{get_bounded_context, Error as BoundedContextError};
+use prophet_mermaid::{EntityDiagramStyle, MermaidString};
+use prophet_model::{
+    normalize, AntiPattern, Diagnostic, Endpoint, Entity, EntityGraph, Metrics, MicroserviceGraph,
+    Severity, UnresolvedCall,
+};
 use serde::Serialize;
 use source_code_parser::{parse_project_context, ressa::RessaResult, Directory};
 
@@ -16,6 +25,8 @@ pub struct Microservice {
     pub name: String,
     /// The entity diagram for the analyzed microservice,
     pub entity_diagram: Option<MermaidString>,
+    /// The HTTP endpoints the microservice exposes
+    pub endpoints: Vec<Endpoint>,
 }
 
 /// The analyzed data for the provided project
@@ -30,16 +41,101 @@ pub struct AppData {
     pub entity_diagram: Option<MermaidString>,
     /// The microservices in the analyzed project
     pub microservices: Vec<Microservice>,
+    /// The calls between microservices that could not be resolved to the
+    /// microservice or endpoint they call
+    pub unresolved_calls: Vec<UnresolvedCall>,
+    /// The objects of the ReSSA result that were skipped because they could not
+    /// be read, and why
+    pub diagnostics: Vec<Diagnostic>,
+    /// The architecture metrics of the analyzed project
+    pub metrics: Metrics,
+    /// The anti-patterns detected in the analyzed project, which are also
+    /// highlighted in the diagrams
+    pub anti_patterns: Vec<AntiPattern>,
+    /// The exact versions of the analyzed repositories
+    pub repositories: Vec<RepositoryVersion>,
+    /// The analyzed graphs, if [`AnalysisOptions::include_graphs`] is set
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub graphs: Option<Graphs>,
+    /// The stages of the analysis that only produced partial results
+    pub warnings: Vec<Warning>,
+}
+
+/// The graphs the diagrams of an [`AppData`] are rendered from
+#[derive(Debug, Clone, Serialize)]
+pub struct Graphs {
+    /// The calls between the microservices
+    pub communication: MicroserviceGraph,
+    /// The merged entities of all microservices, if they could be merged
+    pub entities: Option<EntityGraph>,
+}
+
+/// The result of the stages of an analysis that block the current thread, which
+/// is turned into an [`AppData`] by [`AppData::from_repository_analysis`]
+#[derive(Debug)]
+pub struct RepositoryAnalysis {
+    repositories: Vec<RepositoryVersion>,
+    ms_graph: MicroserviceGraph,
+}
+
+/// The server-side options for analyzing a project
+#[derive(Debug, Clone)]
+pub struct AnalysisOptions {
+    /// The credentials to clone private repositories with
+    pub credentials: Credentials,
+    /// The directory to create the [`Workspace`] of each analysis in
+    pub workspace_root: PathBuf,
+    /// How the entities of all microservices are merged into a bounded context
+    pub bounded_context: BoundedContextOptions,
+    /// The client for the external bounded context service
+    pub bounded_context_client: BoundedContextClient,
+    /// Whether to include the analyzed graphs in the [`AppData`] besides their diagrams
+    pub include_graphs: bool,
+    /// Whether to render the entity diagrams as class diagrams or as entity
+    /// relationship diagrams, which do not highlight anti-patterns
+    pub entity_diagram: EntityDiagramStyle,
+    /// The directory that repositories analyzed in place with [`AppData::from_paths`]
+    /// must be checked out under, if they are restricted to one
+    pub local_root: Option<PathBuf>,
+}
+
+impl Default for AnalysisOptions {
+    fn default() -> Self {
+        AnalysisOptions {
+            credentials: Credentials::default(),
+            workspace_root: std::env::temp_dir().join("prophet"),
+            bounded_context: BoundedContextOptions::default(),
+            bounded_context_client: BoundedContextClient::default(),
+            include_graphs: false,
+            entity_diagram: EntityDiagramStyle::default(),
+            local_root: None,
+        }
+    }
 }
 
 impl AppData {
     /// Creates an AppData from the results of a ReSSA
-    pub async fn from_ressa_result(ressa_result: &RessaResult) -> Result<AppData, Error> {
-        let ms_graph = match MicroserviceGraph::try_new(ressa_result) {
-            Some(ms_graph) => ms_graph,
-            None => return Err(Error::AppData("Could not create microservice graph".into())),
-        };
+    pub async fn from_ressa_result(
+        ressa_result: &RessaResult,
+        options: &AnalysisOptions,
+    ) -> Result<AppData, Error> {
+        AppData::from_ressa_result_inner(ressa_result, options, &mut |_| ()).await
+    }
+
+    async fn from_ressa_result_inner(
+        ressa_result: &RessaResult,
+        options: &AnalysisOptions,
+        on_stage: &mut impl FnMut(Stage),
+    ) -> Result<AppData, Error> {
+        let ms_graph = microservice_graph(ressa_result)?;
+        AppData::from_microservice_graph(ms_graph, options, on_stage).await
+    }
 
+    async fn from_microservice_graph(
+        ms_graph: MicroserviceGraph,
+        options: &AnalysisOptions,
+        on_stage: &mut impl FnMut(Stage),
+    ) -> Result<AppData, Error> {
         let microservices = ms_graph.nodes();
         // Collect all entities from all microservices to be bound
         let entities: Vec<_> = microservices
@@ -48,49 +144,372 @@ impl AppData {
             .cloned()
             .collect();
 
-        // Get the bounded context and its diagram
-        let bounded_entity_graph = get_bounded_context(&entities).await?;
-        let entity_diagram = Some(MermaidString::from(bounded_entity_graph.clone()));
+        // Get the bounded context and its diagram, falling back to the unmerged
+        // entities if they could not be merged
+        on_stage(Stage::MergingEntities);
+        let mut warnings = vec![];
+        let bounded_entity_graph = match get_bounded_context(
+            &entities,
+            &options.bounded_context,
+            &options.bounded_context_client,
+        )
+        .await
+        {
+            Ok(graph) => Some(graph),
+            // Invalid options are the caller's mistake, not a degraded stage
+            Err(err @ BoundedContextError::InvalidOptions(_)) => return Err(err.into()),
+            Err(err) => {
+                tracing::warn!("Falling back to unmerged entities: {}", err);
+                let remote_call = match &err {
+                    BoundedContextError::RemoteCall(remote_call) => Some(remote_call.clone()),
+                    _ => None,
+                };
+                warnings.push(Warning {
+                    remote_call,
+                    ..Warning::new(
+                        Stage::MergingEntities,
+                        format!("Could not merge entities, showing them unmerged: {}", err),
+                    )
+                });
+                let graph = EntityGraph::try_new(&entities);
+                if graph.is_none() {
+                    warnings.push(Warning::new(
+                        Stage::MergingEntities,
+                        "Could not create the unmerged entity graph",
+                    ));
+                }
+                graph
+            }
+        };
+
+        // Flag the entities that could not be attributed to a merged entity, which
+        // the remote service may have merged under a dissimilar name
+        if let Some(graph) = &bounded_entity_graph {
+            warnings.extend(unattributed_warnings(&entities, graph));
+        }
+
+        // Flag the merged entities whose sources are stored in different databases
+        let conflicts = bounded_entity_graph.iter().flat_map(EntityGraph::nodes);
+        for entity in conflicts {
+            let types = entity.conflicting_types();
+            if types.is_empty() {
+                continue;
+            }
+            let sources: Vec<_> = entity
+                .sources
+                .iter()
+                .map(|source| match &source.microservice {
+                    Some(ms) => format!("{}.{} ({})", ms, source.entity, source.ty),
+                    None => format!("{} ({})", source.entity, source.ty),
+                })
+                .collect();
+            warnings.push(Warning::new(
+                Stage::MergingEntities,
+                format!(
+                    "{} was merged from entities stored in different databases: {}",
+                    entity.name,
+                    sources.join(", ")
+                ),
+            ));
+        }
+
+        on_stage(Stage::Rendering);
+        let bounded_context = bounded_entity_graph
+            .as_ref()
+            .map(EntityGraph::nodes)
+            .unwrap_or_default();
+        let anti_patterns = ms_graph.anti_patterns(&bounded_context);
+        let entity_diagram = bounded_entity_graph
+            .clone()
+            .map(|graph| render_entities(graph, &anti_patterns, options.entity_diagram));
 
         // Get the microservice communication diagram
-        let communication_diagram = Some(MermaidString::from(ms_graph));
+        let unresolved_calls = ms_graph.unresolved_calls().to_vec();
+        let diagnostics = ms_graph.diagnostics().to_vec();
+        let metrics = ms_graph.metrics();
+        let graphs = options.include_graphs.then(|| Graphs {
+            communication: ms_graph.clone(),
+            entities: bounded_entity_graph.clone(),
+        });
+        let communication_diagram =
+            Some(MermaidString::from(ms_graph).highlight(service_highlights(&anti_patterns)));
 
         // Get the microservice bounded entity diagrams
         let microservices = microservices
             .into_iter()
             .map(|ms| {
-                let mut entity_graph = bounded_entity_graph.clone();
-                entity_graph.filter_entities(&ms.ref_entities);
+                let entity_diagram = bounded_entity_graph.clone().map(|mut entity_graph| {
+                    entity_graph.filter_owner(&ms.name);
+                    render_entities(entity_graph, &anti_patterns, options.entity_diagram)
+                });
                 Microservice {
                     name: ms.name,
-                    entity_diagram: Some(MermaidString::from(entity_graph)),
+                    entity_diagram,
+                    endpoints: ms.endpoints,
                 }
             })
             .collect();
 
         Ok(AppData {
-            name: "system".into(),
+            name: options.bounded_context.system_name.clone(),
             communication_diagram,
             entity_diagram,
             microservices,
+            unresolved_calls,
+            diagnostics,
+            metrics,
+            anti_patterns,
+            repositories: vec![],
+            graphs,
+            warnings,
         })
     }
 
     /// Clone the provided repositories and generate ReSSAs to analyze them
     /// based on the languages in its LAAST
     pub async fn from_repositories<P: AsRef<Path>>(
+        repos: Repositories,
+        ressa_dir: P,
+        options: &AnalysisOptions,
+    ) -> Result<AppData, Error> {
+        AppData::from_repositories_with_progress(repos, ressa_dir, options, |_| ()).await
+    }
+
+    /// Clone the provided repositories and analyze them like
+    /// [`AppData::from_repositories`], reporting each [`Stage`] of the
+    /// analysis to `on_stage` as it is started
+    ///
+    /// The repositories are cloned into a new [`Workspace`] under the configured
+    /// root, which is removed once the analysis finishes, fails or is dropped
+    pub async fn from_repositories_with_progress<P: AsRef<Path>>(
+        repos: Repositories,
+        ressa_dir: P,
+        options: &AnalysisOptions,
+        mut on_stage: impl FnMut(Stage),
+    ) -> Result<AppData, Error> {
+        let analysis = AppData::analyze_repositories(
+            repos,
+            ressa_dir,
+            options,
+            &mut on_stage,
+            &CancellationToken::default(),
+        )?;
+        AppData::from_repository_analysis(analysis, options, on_stage).await
+    }
+
+    /// Clones, parses and runs the ReSSAs against the provided repositories, which
+    /// are the stages of [`AppData::from_repositories_with_progress`] that block the
+    /// current thread, so they can be run on another thread. The analysis stops
+    /// with [`Error::Cancelled`] once the token is cancelled
+    pub fn analyze_repositories<P: AsRef<Path>>(
         mut repos: Repositories,
         ressa_dir: P,
+        options: &AnalysisOptions,
+        mut on_stage: impl FnMut(Stage),
+        cancel: &CancellationToken,
+    ) -> Result<RepositoryAnalysis, Error> {
+        repos.validate()?;
+        let workspace = Workspace::new(&options.workspace_root)?;
+
+        cancel.check()?;
+        on_stage(Stage::Cloning);
+        repos.clone_all(&workspace, &options.credentials, cancel)?;
+
+        let repositories = repos.versions();
+        let ms_graph = analyze_directory(repos.into(), ressa_dir.as_ref(), &mut on_stage, cancel)?;
+        Ok(RepositoryAnalysis {
+            repositories,
+            ms_graph,
+        })
+        // Clean up the workspace on disk on drop
+    }
+
+    /// Merges the entities and renders the diagrams of repositories analyzed by
+    /// [`AppData::analyze_repositories`]
+    pub async fn from_repository_analysis(
+        analysis: RepositoryAnalysis,
+        options: &AnalysisOptions,
+        mut on_stage: impl FnMut(Stage),
     ) -> Result<AppData, Error> {
-        repos.clone_all()?;
+        let app_data =
+            AppData::from_microservice_graph(analysis.ms_graph, options, &mut on_stage).await?;
+        Ok(AppData {
+            repositories: analysis.repositories,
+            ..app_data
+        })
+    }
 
-        let dir: Directory = repos.into();
-        let mut laast = parse_project_context(&dir)?;
-        // Generate ReSSAs based on languages in ctx modules
-        let result: RessaResult = run_ressa(&mut laast.modules, ressa_dir.as_ref())
-            .map_err(|err| Error::AppData(err.to_string()))?;
+    /// Analyze repositories that are already checked out on disk, without
+    /// cloning or removing them. The repositories must be under the configured
+    /// `local_root`, if any
+    pub async fn from_paths<P: AsRef<Path>>(
+        repos: LocalRepositories,
+        ressa_dir: P,
+        options: &AnalysisOptions,
+    ) -> Result<AppData, Error> {
+        repos.validate(options.local_root.as_deref())?;
+        let repositories = repos.versions();
+        let ms_graph = analyze_directory(
+            repos.into(),
+            ressa_dir.as_ref(),
+            &mut |_| (),
+            &CancellationToken::default(),
+        )?;
+        let app_data = AppData::from_microservice_graph(ms_graph, options, &mut |_| ()).await?;
+        Ok(AppData {
+            repositories,
+            ..app_data
+        })
+    }
+}
 
-        AppData::from_ressa_result(&result).await
-        // Clean up repos on disk on drop
+/// Parse the directory and generate ReSSAs to analyze it based on the
+/// languages in its LAAST
+fn analyze_directory(
+    dir: Directory,
+    ressa_dir: &Path,
+    on_stage: &mut impl FnMut(Stage),
+    cancel: &CancellationToken,
+) -> Result<MicroserviceGraph, Error> {
+    cancel.check()?;
+    on_stage(Stage::Parsing);
+    let mut laast = parse_project_context(&dir)?;
+
+    // Generate ReSSAs based on languages in ctx modules
+    cancel.check()?;
+    on_stage(Stage::RunningRessa);
+    let result: RessaResult =
+        run_ressa(&mut laast.modules, ressa_dir).map_err(|err| Error::AppData(err.to_string()))?;
+
+    // The ReSSA result cannot leave this thread, unlike the graph read from it
+    cancel.check()?;
+    microservice_graph(&result)
+}
+
+/// Warns about the entities that are not a source of any merged entity
+fn unattributed_warnings(entities: &[Entity], graph: &EntityGraph) -> Vec<Warning> {
+    let merged = graph.nodes();
+    entities
+        .iter()
+        .filter(|entity| {
+            entity
+                .source_entities()
+                .iter()
+                .any(|source| !merged.iter().any(|merged| merged.sources.contains(source)))
+        })
+        .map(|entity| {
+            Warning::new(
+                Stage::MergingEntities,
+                format!(
+                    "{} is not similar enough to any merged entity to be attributed to one",
+                    entity.name
+                ),
+            )
+        })
+        .collect()
+}
+
+fn microservice_graph(ressa_result: &RessaResult) -> Result<MicroserviceGraph, Error> {
+    MicroserviceGraph::try_new(ressa_result)
+        .ok_or_else(|| Error::AppData("Could not create microservice graph".into()))
+}
+
+/// The most severe anti-pattern each microservice is part of
+fn service_highlights(anti_patterns: &[AntiPattern]) -> BTreeMap<&str, Severity> {
+    let mut highlights = BTreeMap::new();
+    for anti_pattern in anti_patterns {
+        for service in anti_pattern.services.iter() {
+            let severity = highlights
+                .entry(service.as_str())
+                .or_insert(anti_pattern.severity);
+            *severity = anti_pattern.severity.max(*severity);
+        }
+    }
+    highlights
+}
+
+/// Renders an entity diagram, highlighting the entities that are part of an
+/// anti-pattern in class diagrams. The entities may have been renamed by merging,
+/// so they are matched by their normalized names
+fn render_entities(
+    graph: EntityGraph,
+    anti_patterns: &[AntiPattern],
+    style: EntityDiagramStyle,
+) -> MermaidString {
+    if style != EntityDiagramStyle::Class {
+        return MermaidString::from_entity_graph(graph, style);
+    }
+
+    let mut highlights: BTreeMap<String, Severity> = BTreeMap::new();
+    for entity in graph.nodes() {
+        let name = normalize(&entity.name);
+        let severity = anti_patterns
+            .iter()
+            .filter(|anti_pattern| {
+                anti_pattern
+                    .entities
+                    .iter()
+                    .any(|other| normalize(other) == name)
+            })
+            .map(|anti_pattern| anti_pattern.severity)
+            .max();
+        if let Some(severity) = severity {
+            highlights.insert(entity.name, severity);
+        }
+    }
+
+    MermaidString::from(graph).highlight(
+        highlights
+            .iter()
+            .map(|(name, severity)| (name.as_str(), *severity)),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use prophet_model::DatabaseType;
+
+    use super::*;
+
+    #[test]
+    fn reject_local_paths_outside_local_root() {
+        let dir = std::env::temp_dir().join(format!("prophet-from-paths-{}", std::process::id()));
+        let root = dir.join("root");
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::create_dir_all(dir.join("outside")).unwrap();
+
+        let repos: LocalRepositories = serde_json::from_value(serde_json::json!([
+            { "local_path": dir.join("outside"), "root_dirs": ["."] }
+        ]))
+        .unwrap();
+        let options = AnalysisOptions {
+            local_root: Some(root),
+            ..AnalysisOptions::default()
+        };
+        let result = futures::executor::block_on(AppData::from_paths(repos, &dir, &options));
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(matches!(result, Err(Error::InvalidRepository(_))));
+    }
+
+    #[test]
+    fn warn_about_unattributed_entities() {
+        let orders = Entity::new("Orders", vec![], DatabaseType::MySQL);
+        let shipment = Entity::new("Shipment", vec![], DatabaseType::MySQL);
+        let merged = Entity::merged("Order", vec![], orders.source_entities());
+        let graph = EntityGraph::try_new(&[merged]).unwrap();
+
+        let warnings = unattributed_warnings(&[orders, shipment], &graph);
+        let messages: Vec<_> = warnings
+            .iter()
+            .map(|warning| (warning.stage, warning.message.as_str()))
+            .collect();
+        assert_eq!(
+            vec![(
+                Stage::MergingEntities,
+                "Shipment is not similar enough to any merged entity to be attributed to one"
+            )],
+            messages
+        );
     }
 }
@@ -4,12 +4,16 @@
 pub enum Error {
     #[error("Could not clone repository: {0}")]
     CloneRepo(String),
+    #[error("Invalid repository: {0}")]
+    InvalidRepository(String),
     #[error("Encountered an IO error: {0}")]
     Io(String),
     #[error("Could not create an AppData from the provided ReSSA: {0}")]
     AppData(String),
-    #[error("Could not create bounded context")]
+    #[error("Could not create bounded context: {0}")]
     BoundedContext(#[from] prophet_bounded_context::Error),
+    #[error("The analysis was cancelled")]
+    Cancelled,
 }
 
 macro_rules! error_from_impl {
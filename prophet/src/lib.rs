@@ -3,10 +3,26 @@
 pub(crate) mod repositories;
 pub use repositories::*;
 
+pub(crate) mod workspace;
+pub use workspace::*;
+
+pub(crate) mod credentials;
+pub use credentials::*;
+
 pub(crate) mod error;
 pub use error::*;
 
+pub(crate) mod progress;
+pub use progress::*;
+
 pub(crate) mod app_data;
 pub use app_data::*;
 
-pub(crate) mod adapter;
+pub mod adapter;
+
+pub use prophet_mermaid::EntityDiagramStyle;
+
+pub use prophet_bounded_context::{
+    BoundedContextClient, BoundedContextOptions, BoundedContextOverrides, CircuitBreakerSettings,
+    Error as BoundedContextError, MergeStrategy, RemoteCallError, SimilarityMetric, TlsSettings,
+};
@@ -0,0 +1,72 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+use serde::Serialize;
+
+use crate::{Error, RemoteCallError};
+
+/// A stage of the analysis pipeline, reported as the analysis progresses
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Stage {
+    /// Cloning the microservice repositories
+    Cloning,
+    /// Parsing the source code into a LAAST
+    Parsing,
+    /// Running the ReSSAs against the LAAST
+    RunningRessa,
+    /// Merging the entities of all microservices into a bounded context
+    MergingEntities,
+    /// Rendering the diagrams for the analyzed project
+    Rendering,
+}
+
+/// Cancels an analysis running on another thread. The analysis stops before
+/// starting its next stage, or while cloning, once the token is cancelled
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    /// Cancels the analysis using this token, or any of its clones
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    /// Whether the analysis was cancelled
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    /// Fails with [`Error::Cancelled`] if the analysis was cancelled
+    pub(crate) fn check(&self) -> Result<(), Error> {
+        if self.is_cancelled() {
+            Err(Error::Cancelled)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// A stage of the analysis that produced partial results instead of failing
+#[derive(Debug, Clone, Serialize)]
+pub struct Warning {
+    /// The stage that degraded
+    pub stage: Stage,
+    /// Why the stage degraded and what was done instead
+    pub message: String,
+    /// The failed call to the bounded context service, if that degraded the stage
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub remote_call: Option<RemoteCallError>,
+}
+
+impl Warning {
+    pub fn new(stage: Stage, message: impl Into<String>) -> Self {
+        Warning {
+            stage,
+            message: message.into(),
+            remote_call: None,
+        }
+    }
+}
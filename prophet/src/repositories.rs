@@ -1,9 +1,14 @@
-use std::path::{Path, PathBuf};
+use std::path::{Component, Path, PathBuf};
 
-use git2::Repository;
-use serde::Deserialize;
+use git2::{
+    build::{CheckoutBuilder, RepoBuilder},
+    FetchOptions, Repository,
+};
+use serde::{Deserialize, Serialize};
 use source_code_parser::Directory;
 
+use crate::{credentials::redact_userinfo, CancellationToken, Credentials, Error, Workspace};
+
 /// A cloned microservice or microservice system repository
 #[derive(Debug, Default, Deserialize)]
 pub struct MicroservicesRepository {
@@ -12,30 +17,116 @@ pub struct MicroservicesRepository {
     /// The root directories to include source code files from in static analysis,
     /// relative to `clone_dir`
     pub root_dirs: Vec<PathBuf>,
-    /// The local directory the repository was cloned to
+    /// The local directory the repository was cloned to, which is allocated
+    /// within the analysis' [`Workspace`] and never provided by the caller
+    #[serde(skip)]
     pub clone_dir: PathBuf,
+    /// The branch to check out instead of the default branch
+    pub branch: Option<String>,
+    /// The tag to check out instead of the default branch
+    pub tag: Option<String>,
+    /// The revision (commit SHA or any other revspec) to check out instead
+    /// of the default branch
+    pub rev: Option<String>,
+    /// The SHA of the commit checked out after cloning
+    #[serde(skip)]
+    pub commit: Option<String>,
 }
 
 impl MicroservicesRepository {
-    /// Clones a microservice(s) repository
-    pub fn clone(&mut self) -> Result<(), git2::Error> {
-        Repository::clone(&self.git_url, &self.clone_dir)?;
+    /// Clones a microservice(s) repository, checking out the requested
+    /// branch, tag or revision if one was provided. The transfer is stopped
+    /// once the token is cancelled
+    pub fn clone(
+        &mut self,
+        credentials: &Credentials,
+        cancel: &CancellationToken,
+    ) -> Result<(), git2::Error> {
+        let pins = [&self.branch, &self.tag, &self.rev];
+        if pins.iter().filter(|pin| pin.is_some()).count() > 1 {
+            return Err(git2::Error::from_str(
+                "Only one of branch, tag or rev may be provided",
+            ));
+        }
+
+        let mut callbacks = credentials.remote_callbacks();
+        callbacks.transfer_progress(|_| !cancel.is_cancelled());
+        let mut fetch_options = FetchOptions::new();
+        fetch_options.remote_callbacks(callbacks);
+
+        let mut builder = RepoBuilder::new();
+        builder.fetch_options(fetch_options);
+        if let Some(branch) = &self.branch {
+            builder.branch(branch);
+        }
+        let repo = builder.clone(&self.git_url, &self.clone_dir)?;
+
+        // Detach the HEAD at the requested tag or revision
+        let spec = match (&self.tag, &self.rev) {
+            (Some(tag), _) => Some(format!("refs/tags/{}", tag)),
+            (_, Some(rev)) => Some(rev.clone()),
+            _ => None,
+        };
+        if let Some(spec) = spec {
+            let commit = repo.revparse_single(&spec)?.peel_to_commit()?;
+            repo.checkout_tree(commit.as_object(), Some(CheckoutBuilder::new().force()))?;
+            repo.set_head_detached(commit.id())?;
+        }
+
+        self.commit = Some(head_commit(&repo)?);
         Ok(())
     }
 }
 
-impl Drop for MicroservicesRepository {
-    /// Clean the cloned repository when freeing the repository from memory
-    fn drop(&mut self) {
-        if let Err(err) = std::fs::remove_dir_all(&self.clone_dir) {
-            tracing::warn!(
-                "Failed to remove cloned repository '{}' at '{:?}': {:?}",
-                self.git_url,
-                self.clone_dir,
-                err
-            );
+/// Gets the SHA of the commit the repository's HEAD points to
+fn head_commit(repo: &Repository) -> Result<String, git2::Error> {
+    Ok(repo.head()?.peel_to_commit()?.id().to_string())
+}
+
+/// The exact source version of an analyzed repository
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct RepositoryVersion {
+    /// The Git URL or local path of the repository
+    pub source: String,
+    /// The SHA of the analyzed commit, if it could be resolved
+    pub commit: Option<String>,
+}
+
+/// Ensures all root directories are relative paths that stay within the repository
+fn validate_root_dirs(root_dirs: &[PathBuf]) -> Result<(), Error> {
+    for root_dir in root_dirs {
+        let escapes = root_dir.components().any(|component| {
+            matches!(
+                component,
+                Component::Prefix(_) | Component::RootDir | Component::ParentDir
+            )
+        });
+        if escapes {
+            return Err(Error::InvalidRepository(format!(
+                "Root directory '{}' must be a relative path without '..'",
+                root_dir.display()
+            )));
         }
     }
+    Ok(())
+}
+
+/// Ensures a local repository is checked out under the canonical `local_root`,
+/// after resolving any symbolic links and `..` in its path
+fn validate_local_path(local_path: &Path, local_root: &Path) -> Result<(), Error> {
+    let within_root = matches!(
+        local_path.canonicalize(),
+        Ok(local_path) if local_path.starts_with(local_root)
+    );
+    if within_root {
+        Ok(())
+    } else {
+        Err(Error::InvalidRepository(format!(
+            "Local path '{}' must be an existing directory under '{}'",
+            local_path.display(),
+            local_root.display()
+        )))
+    }
 }
 
 /// Gets the subdirectories and files from a directory `&Path`
@@ -80,28 +171,33 @@ fn convert_sub_dirs(sub_dirs: Vec<PathBuf>) -> Vec<Directory> {
     sub_dirs.into_iter().flat_map(convert_sub_dir).collect()
 }
 
+/// Converts a repository on disk into its Directory representation, including
+/// only the files directly in `root` and the given `root_dirs` relative to it
+fn into_directory(root: &Path, root_dirs: &[PathBuf]) -> Directory {
+    // Convert into the Directory type from the given root directories
+    let root_dirs = root_dirs
+        .iter()
+        .map(|relative_path| {
+            let mut root = root.to_path_buf();
+            root.push(relative_path);
+            root
+        })
+        .flat_map(convert_sub_dir)
+        .collect();
+
+    // Get the files in the root directory
+    let files = match get_dir_contents(root) {
+        Ok((files, _)) => files,
+        _ => vec![],
+    };
+
+    Directory::new(files, root_dirs, root.to_path_buf())
+}
+
 impl From<MicroservicesRepository> for Directory {
     /// Create a Directory structure from a cloned microservice(s) repository
     fn from(repo: MicroservicesRepository) -> Self {
-        // Convert into the Directory type from the given MS repository
-        let root_dirs = repo
-            .root_dirs
-            .iter()
-            .map(|relative_path| {
-                let mut root = repo.clone_dir.clone();
-                root.push(relative_path);
-                root
-            })
-            .flat_map(convert_sub_dir)
-            .collect();
-
-        // Get the files in the root directory
-        let files = match get_dir_contents(&repo.clone_dir.clone()) {
-            Ok((files, _)) => files,
-            _ => vec![],
-        };
-
-        Directory::new(files, root_dirs, repo.clone_dir.clone())
+        into_directory(&repo.clone_dir, &repo.root_dirs)
     }
 }
 
@@ -113,10 +209,14 @@ impl From<MicroservicesRepository> for Directory {
 ///   {
 ///      "git_url": "https://github.com/some/repository.git",
 ///      "root_dirs": ["some/relative", "./paths/here"],
-///      "clone_dir": "/path/to/the/cloned/repo"
+///      "tag": "v1.0.0"
 ///   }
 /// ]
 /// ```
+///
+/// At most one of the optional `branch`, `tag` or `rev` fields may be provided
+/// per repository to analyze that version instead of the default branch. The
+/// `root_dirs` must be relative paths that do not contain `..`.
 #[derive(Debug, Deserialize)]
 pub struct Repositories(Vec<MicroservicesRepository>);
 
@@ -137,11 +237,247 @@ impl From<Repositories> for Directory {
 }
 
 impl Repositories {
-    /// Clones all of the microservice(s) repositories
-    pub fn clone_all(&mut self) -> Result<(), git2::Error> {
-        for repo in self.0.iter_mut() {
-            repo.clone()?;
+    /// Ensures all of the repositories only include directories within themselves
+    pub fn validate(&self) -> Result<(), Error> {
+        self.0
+            .iter()
+            .try_for_each(|repo| validate_root_dirs(&repo.root_dirs))
+    }
+
+    /// Clones all of the microservice(s) repositories into the workspace,
+    /// authenticating with the provided credentials if needed
+    pub fn clone_all(
+        &mut self,
+        workspace: &Workspace,
+        credentials: &Credentials,
+        cancel: &CancellationToken,
+    ) -> Result<(), Error> {
+        for (i, repo) in self.0.iter_mut().enumerate() {
+            cancel.check()?;
+            repo.clone_dir = workspace.path().join(i.to_string());
+            repo.clone(credentials, cancel).map_err(|err| {
+                if cancel.is_cancelled() {
+                    Error::Cancelled
+                } else {
+                    Error::CloneRepo(credentials.redact(&err.to_string()))
+                }
+            })?;
         }
         Ok(())
     }
+
+    /// Gets the checked out versions of the cloned repositories
+    pub fn versions(&self) -> Vec<RepositoryVersion> {
+        self.0
+            .iter()
+            .map(|repo| RepositoryVersion {
+                source: redact_userinfo(&repo.git_url),
+                commit: repo.commit.clone(),
+            })
+            .collect()
+    }
+}
+
+/// A microservice or microservice system repository that is already checked out
+/// on disk. Unlike a [`MicroservicesRepository`], it is never removed from disk
+#[derive(Debug, Default, Deserialize)]
+pub struct LocalRepository {
+    /// The local directory the repository is checked out in
+    pub local_path: PathBuf,
+    /// The root directories to include source code files from in static analysis,
+    /// relative to `local_path`
+    pub root_dirs: Vec<PathBuf>,
+}
+
+impl From<LocalRepository> for Directory {
+    /// Create a Directory structure from a local microservice(s) repository
+    fn from(repo: LocalRepository) -> Self {
+        into_directory(&repo.local_path, &repo.root_dirs)
+    }
+}
+
+/// The local repositories for the microservices to statically analyze
+///
+/// The serialized representation in JSON is as follows
+/// ```json
+/// [
+///   {
+///      "local_path": "/path/to/the/checked/out/repo",
+///      "root_dirs": ["some/relative", "./paths/here"]
+///   }
+/// ]
+/// ```
+#[derive(Debug, Deserialize)]
+pub struct LocalRepositories(Vec<LocalRepository>);
+
+impl From<LocalRepositories> for Directory {
+    /// Create a Directory structure from local microservice repositories
+    fn from(repositories: LocalRepositories) -> Self {
+        let sub_directories = repositories
+            .0
+            .into_iter()
+            .map(LocalRepository::into)
+            .collect::<Vec<Directory>>();
+
+        // Create a fake top-level directory
+        Directory::new(vec![], sub_directories, "".into())
+    }
+}
+
+impl LocalRepositories {
+    /// Ensures all of the repositories only include directories within themselves,
+    /// and that they are checked out under `local_root` if it is provided
+    pub fn validate(&self, local_root: Option<&Path>) -> Result<(), Error> {
+        let local_root = local_root.map(Path::canonicalize).transpose()?;
+        self.0.iter().try_for_each(|repo| {
+            if let Some(local_root) = &local_root {
+                validate_local_path(&repo.local_path, local_root)?;
+            }
+            validate_root_dirs(&repo.root_dirs)
+        })
+    }
+
+    /// Gets the checked out versions of the local repositories, if they are
+    /// Git repositories
+    pub fn versions(&self) -> Vec<RepositoryVersion> {
+        self.0
+            .iter()
+            .map(|repo| RepositoryVersion {
+                source: repo.local_path.to_string_lossy().into_owned(),
+                commit: Repository::discover(&repo.local_path)
+                    .and_then(|repo| head_commit(&repo))
+                    .ok(),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn local(local_path: PathBuf) -> LocalRepositories {
+        LocalRepositories(vec![LocalRepository {
+            local_path,
+            root_dirs: vec![],
+        }])
+    }
+
+    #[test]
+    fn keep_root_dirs_within_repository() {
+        let valid = |root_dir: &str| validate_root_dirs(&[PathBuf::from(root_dir)]).is_ok();
+
+        assert!(valid("src/main/java"));
+        assert!(valid("./services/orders"));
+        assert!(valid("services/..orders"));
+        assert!(!valid("../other"));
+        assert!(!valid("services/../../other"));
+        assert!(!valid("/etc"));
+        assert!(validate_root_dirs(&[]).is_ok());
+        assert!(validate_root_dirs(&["src".into(), "../src".into()]).is_err());
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn reject_root_dirs_with_prefix() {
+        assert!(validate_root_dirs(&[PathBuf::from(r"C:\Windows")]).is_err());
+        assert!(validate_root_dirs(&[PathBuf::from(r"C:relative")]).is_err());
+    }
+
+    #[test]
+    fn restrict_local_paths_to_root() {
+        let dir = std::env::temp_dir().join(format!("prophet-local-root-{}", std::process::id()));
+        let root = dir.join("root");
+        std::fs::create_dir_all(root.join("repo")).unwrap();
+        std::fs::create_dir_all(dir.join("outside")).unwrap();
+
+        assert!(local(root.join("repo")).validate(Some(&root)).is_ok());
+        assert!(local(dir.join("outside")).validate(Some(&root)).is_err());
+        assert!(local(root.join("repo/../../outside"))
+            .validate(Some(&root))
+            .is_err());
+        assert!(local(root.join("missing")).validate(Some(&root)).is_err());
+        assert!(local(dir.join("outside")).validate(None).is_ok());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn reject_several_pins() {
+        let mut repo = MicroservicesRepository {
+            git_url: "https://github.com/example/system.git".into(),
+            branch: Some("main".into()),
+            tag: Some("v1.0.0".into()),
+            ..Default::default()
+        };
+        let err = repo
+            .clone(&Credentials::default(), &CancellationToken::default())
+            .unwrap_err();
+        assert_eq!(
+            "Only one of branch, tag or rev may be provided",
+            err.message()
+        );
+        assert_eq!(None, repo.commit);
+    }
+
+    #[test]
+    fn report_checked_out_commit() {
+        let dir = std::env::temp_dir().join(format!("prophet-version-{}", std::process::id()));
+        let bare = Repository::init_bare(dir.join("bare")).unwrap();
+        let signature = git2::Signature::now("prophet", "prophet@example.com").unwrap();
+        let tree = bare
+            .find_tree(bare.treebuilder(None).unwrap().write().unwrap())
+            .unwrap();
+        let first = bare
+            .commit(Some("HEAD"), &signature, &signature, "First", &tree, &[])
+            .unwrap();
+        bare.tag_lightweight("v1", &bare.find_object(first, None).unwrap(), false)
+            .unwrap();
+        let parent = bare.find_commit(first).unwrap();
+        let second = bare
+            .commit(
+                Some("HEAD"),
+                &signature,
+                &signature,
+                "Second",
+                &tree,
+                &[&parent],
+            )
+            .unwrap();
+
+        let git_url = dir.join("bare").to_string_lossy().into_owned();
+        let mut repos = Repositories(vec![
+            MicroservicesRepository {
+                git_url: git_url.clone(),
+                clone_dir: dir.join("head"),
+                ..Default::default()
+            },
+            MicroservicesRepository {
+                git_url: git_url.clone(),
+                clone_dir: dir.join("tag"),
+                tag: Some("v1".into()),
+                ..Default::default()
+            },
+        ]);
+        for repo in repos.0.iter_mut() {
+            repo.clone(&Credentials::default(), &CancellationToken::default())
+                .unwrap();
+        }
+        let commits: Vec<_> = repos
+            .versions()
+            .into_iter()
+            .map(|version| (version.source, version.commit))
+            .collect();
+        let local_commit = local(dir.join("bare")).versions()[0].commit.clone();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(
+            vec![
+                (git_url.clone(), Some(second.to_string())),
+                (git_url, Some(first.to_string())),
+            ],
+            commits
+        );
+        assert_eq!(Some(second.to_string()), local_commit);
+    }
 }
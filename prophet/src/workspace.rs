@@ -0,0 +1,80 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicU64, Ordering},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::Error;
+
+/// A unique directory that the repositories of a single analysis are cloned
+/// into. The directory and everything in it is removed when the workspace is
+/// dropped, including when the analysis panics or is cancelled
+#[derive(Debug)]
+pub struct Workspace {
+    dir: PathBuf,
+}
+
+impl Workspace {
+    /// Creates a new, empty workspace under the provided root directory
+    pub fn new<P: AsRef<Path>>(root: P) -> Result<Workspace, Error> {
+        static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+        std::fs::create_dir_all(root.as_ref())?;
+        loop {
+            let nanos = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |time| time.subsec_nanos());
+            let name = format!(
+                "prophet-{}-{}-{}",
+                std::process::id(),
+                NEXT_ID.fetch_add(1, Ordering::Relaxed),
+                nanos
+            );
+            let dir = root.as_ref().join(name);
+
+            // Never reuse a directory that already exists, it may not be ours
+            match std::fs::create_dir(&dir) {
+                Ok(()) => return Ok(Workspace { dir }),
+                Err(err) if err.kind() == std::io::ErrorKind::AlreadyExists => continue,
+                Err(err) => return Err(err.into()),
+            }
+        }
+    }
+
+    /// Gets the path of the workspace directory
+    pub fn path(&self) -> &Path {
+        &self.dir
+    }
+}
+
+impl Drop for Workspace {
+    /// Clean the cloned repositories when freeing the workspace from memory
+    fn drop(&mut self) {
+        if let Err(err) = std::fs::remove_dir_all(&self.dir) {
+            tracing::warn!("Failed to remove workspace at '{:?}': {:?}", self.dir, err);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn remove_unique_workspaces_on_drop() {
+        let root = std::env::temp_dir().join(format!("prophet-workspaces-{}", std::process::id()));
+        let first = Workspace::new(&root).unwrap();
+        let second = Workspace::new(&root).unwrap();
+        let paths = [first.path().to_path_buf(), second.path().to_path_buf()];
+        assert_ne!(paths[0], paths[1]);
+        assert!(paths.iter().all(|path| path.is_dir()));
+
+        // Everything cloned into a workspace is removed along with it
+        std::fs::write(paths[0].join("file"), "contents").unwrap();
+        drop(first);
+        drop(second);
+        assert!(paths.iter().all(|path| !path.exists()));
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+}